@@ -0,0 +1,833 @@
+//! The command model based upon the [Commands] enum and the parsing method [str_to_command],
+//! which dispatches through the command table in [registry](crate::registry).
+//!
+//! Supports the management of LED states on/off/toggle(ms).
+//!
+//! Supports adaption of the interval of a [Commands::UartStatusReport]
+//!
+//! Supports single and continuous measurements of the DS18B20, see [Commands::TemperatureSensor]
+//!
+//! Supports the sampling period of each sensor in a continuous measurement, see [Commands::Sample]
+//!
+//! The [Display](fmt::Display) implementation of [Commands] is the canonical text form that is
+//! accepted by [str_to_command], it is used to acknowledge commands on the console.
+//!
+//! Malformed input is reported by a [CommandParseError] that carries the offending token,
+//! such that it can be echoed back to the operator.
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::led::LedState;
+use crate::registry::{self, Choices, COMMANDS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightSensorCommands {
+    Off,
+    SingleMeasurment,
+    ContiniousMeasurement,
+
+    /// selects the resolution mode of the following measurements
+    Resolution(LightResolution),
+
+    /// sets the measurement time register in the range [MTREG_RANGE]
+    MeasurementTime(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemperatureSensorCommands {
+    Off,
+    SingleMeasurement,
+    ContinuousMeasurement,
+
+    /// sets the resolution in bits in the range [TEMP_RESOLUTION_RANGE] and persists it
+    Resolution(u8),
+
+    /// sets the lower and upper alarm limits in °C in the range [TEMP_RANGE] and persists them
+    Alarm(i8, i8),
+}
+
+/// The resolution modes of the BH1750FVI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightResolution {
+    /// H-resolution mode, 1 lx
+    High,
+
+    /// H-resolution mode 2, 0.5 lx
+    High2,
+
+    /// L-resolution mode, 4 lx
+    Low,
+}
+
+/// The number of BH1750FVI light sensors on the bus, their ids are 1 and 2
+pub const LIGHT_SENSOR_COUNT: usize = 2;
+
+/// The names of the light sensors in the order of their ids, they may be used instead of the id
+pub const LIGHT_SENSOR_NAMES: [&str; LIGHT_SENSOR_COUNT] = ["indoor", "window"];
+
+/// The valid values of the BH1750FVI measurement time register
+pub const MTREG_RANGE: core::ops::RangeInclusive<u32> = 31..=254;
+
+/// The resolutions of the DS18B20 in bits
+pub const TEMP_RESOLUTION_RANGE: core::ops::RangeInclusive<u32> = 9..=12;
+
+/// The temperatures the DS18B20 measures in °C, the alarm limits are within them
+pub const TEMP_RANGE: core::ops::RangeInclusive<i32> = -55..=125;
+
+/// The sampling periods in ms, a sensor is not sampled faster than its conversion allows
+pub const SAMPLE_PERIOD_RANGE: core::ops::RangeInclusive<u32> = 10..=3_600_000;
+
+/// Operations on the I2C bus itself, used to bring up new parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum I2cCommands {
+    /// probes every address and lists the ones that acknowledge
+    Scan,
+
+    /// writes the bytes to the device at the given address
+    Write(u8, I2cBytes),
+
+    /// reads the given number of bytes from the device at the given address
+    Read(u8, u8),
+
+    /// writes the bytes and reads the given number of bytes after a repeated start
+    WriteRead(u8, I2cBytes, u8),
+}
+
+/// Operations on the 1-Wire bus itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OneWireCommands {
+    /// enumerates the devices by their ROM
+    Scan,
+
+    /// enumerates the devices whose last conversion is beyond their alarm limits
+    AlarmSearch,
+}
+
+/// The most bytes a raw I2C transfer of the console writes or reads
+pub const I2C_MAX_LEN: usize = 16;
+
+/// The bytes of a raw I2C transfer, at most [I2C_MAX_LEN]
+///
+/// The [Display](fmt::Display) gives two hex digits per byte, e.g. `0110`, as they are parsed
+/// by [I2cBytes::from_hex].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct I2cBytes {
+    len: u8,
+    data: [u8; I2C_MAX_LEN],
+}
+
+impl I2cBytes {
+    /// Copies the bytes, None if there are more than [I2C_MAX_LEN]
+    pub fn new(bytes: &[u8]) -> Option<Self> {
+        let mut data = [0u8; I2C_MAX_LEN];
+        data.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        Some(I2cBytes {
+            len: bytes.len() as u8,
+            data,
+        })
+    }
+
+    /// Parses two hex digits per byte, None if the text is empty, malformed or too long
+    pub fn from_hex(text: &str) -> Option<Self> {
+        if text.is_empty() || text.len() % 2 != 0 || text.len() > 2 * I2C_MAX_LEN {
+            return None;
+        }
+        let mut data = [0u8; I2C_MAX_LEN];
+        for (byte, digits) in data.iter_mut().zip(text.as_bytes().chunks(2)) {
+            let digits = core::str::from_utf8(digits).ok()?;
+            if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+                return None;
+            }
+            *byte = u8::from_str_radix(digits, 16).ok()?;
+        }
+        Some(I2cBytes {
+            len: (text.len() / 2) as u8,
+            data,
+        })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..(self.len as usize).min(I2C_MAX_LEN)]
+    }
+}
+
+/// The state the light sensor is operated in, it is shown in the status report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightSensorState {
+    PowerOff,
+    SingleMeasurement,
+    ContiniousMeasurement,
+}
+
+impl LightSensorState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LightSensorState::PowerOff => "off",
+            LightSensorState::SingleMeasurement => "single",
+            LightSensorState::ContiniousMeasurement => "on",
+        }
+    }
+
+    /// The inverse of [LightSensorState::as_str]
+    pub fn parse(state: &str) -> Option<Self> {
+        match state {
+            "off" => Some(LightSensorState::PowerOff),
+            "single" => Some(LightSensorState::SingleMeasurement),
+            "on" => Some(LightSensorState::ContiniousMeasurement),
+            _ => None,
+        }
+    }
+}
+
+impl From<LightSensorState> for LightSensorCommands {
+    /// Gives the command that leads to the given state
+    fn from(state: LightSensorState) -> Self {
+        match state {
+            LightSensorState::PowerOff => LightSensorCommands::Off,
+            LightSensorState::SingleMeasurement => LightSensorCommands::SingleMeasurment,
+            LightSensorState::ContiniousMeasurement => LightSensorCommands::ContiniousMeasurement,
+        }
+    }
+}
+
+/// The state the temperature sensor is operated in, it is shown in the status report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemperatureSensorState {
+    PowerOff,
+    SingleMeasurement,
+    ContinuousMeasurement,
+}
+
+impl TemperatureSensorState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemperatureSensorState::PowerOff => "off",
+            TemperatureSensorState::SingleMeasurement => "single",
+            TemperatureSensorState::ContinuousMeasurement => "on",
+        }
+    }
+
+    /// The inverse of [TemperatureSensorState::as_str]
+    pub fn parse(state: &str) -> Option<Self> {
+        match state {
+            "off" => Some(TemperatureSensorState::PowerOff),
+            "single" => Some(TemperatureSensorState::SingleMeasurement),
+            "on" => Some(TemperatureSensorState::ContinuousMeasurement),
+            _ => None,
+        }
+    }
+}
+
+impl From<TemperatureSensorState> for TemperatureSensorCommands {
+    /// Gives the command that leads to the given state
+    fn from(state: TemperatureSensorState) -> Self {
+        match state {
+            TemperatureSensorState::PowerOff => TemperatureSensorCommands::Off,
+            TemperatureSensorState::SingleMeasurement => TemperatureSensorCommands::SingleMeasurement,
+            TemperatureSensorState::ContinuousMeasurement => TemperatureSensorCommands::ContinuousMeasurement,
+        }
+    }
+}
+
+/// The parts of the state that can be read back with [Commands::Get]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Query {
+    /// the state of the LED with the given id
+    Led(u8),
+
+    /// the interval of the uart status report
+    Status,
+
+    /// the state and last value of the light sensor with the given id
+    Light(u8),
+
+    /// the state and last value of the temperature sensor
+    Temperature,
+
+    /// the sampling period of the given sensor
+    Sample(SensorId),
+}
+
+impl Query {
+    /// All queries in the order they are answered by [Commands::State]
+    pub const ALL: [Query; 10] = [
+        Query::Led(1),
+        Query::Led(2),
+        Query::Led(3),
+        Query::Status,
+        Query::Light(1),
+        Query::Light(2),
+        Query::Temperature,
+        Query::Sample(SensorId::Light(1)),
+        Query::Sample(SensorId::Light(2)),
+        Query::Sample(SensorId::Temperature),
+    ];
+}
+
+/// A sensor of the platform as it is named on the console, e.g. `light 1` or `temp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SensorId {
+    /// the light sensor with the given id
+    Light(u8),
+
+    /// the temperature sensor on the 1-Wire bus
+    Temperature,
+}
+
+/// The encoding used on the developer console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsoleMode {
+    /// human readable lines as parsed by [str_to_command]
+    Text,
+
+    /// COBS framed binary messages, see [frame](crate::frame)
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Commands {
+    /// sets the interval for the uart status report
+    UartStatusReport(u32),
+
+    /// sets a new LEDState for the given LED id
+    Led(u8, LedState),
+
+    /// using a I2C connection to the BH1750FVI with the given id
+    LightSensor(u8, LightSensorCommands),
+
+    /// using the 1-Wire connection to the DS18B20
+    TemperatureSensor(TemperatureSensorCommands),
+
+    /// reads back a part of the state in the syntax of the command that sets it
+    Get(Query),
+
+    /// reads back the whole state, like a [Commands::Get] for every [Query]
+    State,
+
+    /// switches the encoding of the developer console
+    Mode(ConsoleMode),
+
+    /// raw access to the shared I2C bus
+    I2cBus(I2cCommands),
+
+    /// raw access to the 1-Wire bus
+    OneWireBus(OneWireCommands),
+
+    /// sets the period in ms the given sensor is sampled with in a continuous measurement
+    Sample(SensorId, u32),
+
+    /// lists all commands or the usage of the command with the given index in [COMMANDS]
+    Help(Option<u8>),
+}
+
+impl fmt::Display for LightSensorCommands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LightSensorCommands::Off => f.write_str("off"),
+            LightSensorCommands::SingleMeasurment => f.write_str("single"),
+            LightSensorCommands::ContiniousMeasurement => f.write_str("continious"),
+            LightSensorCommands::Resolution(resolution) => write!(f, "mode {}", resolution),
+            LightSensorCommands::MeasurementTime(mtreg) => write!(f, "mtreg {}", mtreg),
+        }
+    }
+}
+
+impl fmt::Display for TemperatureSensorCommands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemperatureSensorCommands::Off => f.write_str("off"),
+            TemperatureSensorCommands::SingleMeasurement => f.write_str("single"),
+            TemperatureSensorCommands::ContinuousMeasurement => f.write_str("continuous"),
+            TemperatureSensorCommands::Resolution(bits) => write!(f, "res {}", bits),
+            TemperatureSensorCommands::Alarm(lo, hi) => write!(f, "alarm {} {}", lo, hi),
+        }
+    }
+}
+
+impl fmt::Display for LightResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LightResolution::High => f.write_str("hr"),
+            LightResolution::High2 => f.write_str("hr2"),
+            LightResolution::Low => f.write_str("lr"),
+        }
+    }
+}
+
+impl fmt::Display for I2cCommands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            I2cCommands::Scan => f.write_str("scan"),
+            I2cCommands::Write(addr, bytes) => write!(f, "write 0x{:02x} {}", addr, bytes),
+            I2cCommands::Read(addr, len) => write!(f, "read 0x{:02x} {}", addr, len),
+            I2cCommands::WriteRead(addr, bytes, len) => write!(f, "wr 0x{:02x} {} {}", addr, bytes, len),
+        }
+    }
+}
+
+impl fmt::Display for OneWireCommands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OneWireCommands::Scan => f.write_str("scan"),
+            OneWireCommands::AlarmSearch => f.write_str("alarms"),
+        }
+    }
+}
+
+impl fmt::Display for I2cBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.as_slice() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConsoleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleMode::Text => f.write_str("text"),
+            ConsoleMode::Binary => f.write_str("binary"),
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Led(id) => write!(f, "led {}", led_id_to_str(*id)),
+            Query::Status => f.write_str("status"),
+            Query::Light(id) => write!(f, "light {}", id),
+            Query::Temperature => f.write_str("temp"),
+            Query::Sample(sensor) => {
+                f.write_str("sample ")?;
+                write_sensor_token(f, sensor)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SensorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorId::Light(id) => write!(f, "light {}", id),
+            SensorId::Temperature => f.write_str("temp"),
+        }
+    }
+}
+
+impl fmt::Display for Commands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Commands::UartStatusReport(ms) => write!(f, "status {}", ms),
+            Commands::Led(id, state) => write!(f, "led {} {}", led_id_to_str(*id), state),
+            Commands::LightSensor(id, sub_cmd) => write!(f, "light {} {}", id, sub_cmd),
+            Commands::TemperatureSensor(sub_cmd) => write!(f, "temp {}", sub_cmd),
+            Commands::Get(query) => write!(f, "get {}", query),
+            Commands::State => f.write_str("state"),
+            Commands::Mode(mode) => write!(f, "mode {}", mode),
+            Commands::I2cBus(op) => write!(f, "i2c {}", op),
+            Commands::OneWireBus(op) => write!(f, "onewire {}", op),
+            Commands::Sample(sensor, ms) => {
+                f.write_str("sample ")?;
+                write_sensor_token(f, sensor)?;
+                write!(f, " {}", ms)
+            }
+            Commands::Help(None) => f.write_str("help"),
+            Commands::Help(Some(idx)) => {
+                let name = COMMANDS.get(*idx as usize).map(|spec| spec.name).unwrap_or("?");
+                write!(f, "help {}", name)
+            }
+        }
+    }
+}
+
+/// Writes the sensor as the single token used by [Commands::Sample], the light sensors by their id
+fn write_sensor_token(f: &mut fmt::Formatter<'_>, sensor: &SensorId) -> fmt::Result {
+    match sensor {
+        SensorId::Light(id) => write!(f, "{}", id),
+        SensorId::Temperature => f.write_str("temp"),
+    }
+}
+
+/// Gives the short color code of the LED with the given id as used by [Commands::Led]
+pub fn led_id_to_str(id: u8) -> &'static str {
+    match id {
+        1 => "r",
+        2 => "g",
+        3 => "b",
+        _ => "?",
+    }
+}
+
+/// The reasons why [str_to_command] rejects a message, each variant borrows the bad token
+///
+/// A token that is not one of the choices of its argument comes with the choices of the
+/// [registry](crate::registry), such that the message lists what the schema accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandParseError<'a> {
+    /// the first word is not a known command verb, empty if the message was blank
+    UnknownCommand(&'a str),
+
+    /// the named argument of the given command is missing
+    MissingArgument(&'a str, &'static str),
+
+    /// a number was expected but the token cannot be parsed as such
+    BadNumber(&'a str),
+
+    /// the token is not one of the LED color codes
+    UnknownLedColor(&'a str, &'static [&'static str]),
+
+    /// the token is neither the id nor the name of a light sensor
+    UnknownLightSensor(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the console encodings
+    UnknownConsoleMode(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the parts of the state that `get` reads back
+    UnknownQuery(&'a str, &'static [&'static str]),
+
+    /// the token names neither a light sensor nor the temperature sensor
+    UnknownSensor(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the light sensor sub-commands
+    UnknownLightCommand(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the temperature sensor sub-commands
+    UnknownTemperatureCommand(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the light sensor resolution modes
+    UnknownLightResolution(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the operations on the I2C bus
+    UnknownI2cCommand(&'a str, &'static [&'static str]),
+
+    /// the token is not one of the operations on the 1-Wire bus
+    UnknownOneWireCommand(&'a str, &'static [&'static str]),
+
+    /// the number is outside of the given inclusive range
+    OutOfRange(&'a str, i64, i64),
+
+    /// the token is not a hex I2C address in the range 0x08..=0x77
+    BadAddress(&'a str),
+
+    /// the token is not a string of hex digits with at most [I2C_MAX_LEN] bytes
+    BadHex(&'a str),
+}
+
+impl fmt::Display for CommandParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandParseError::UnknownCommand(verb) => write!(f, "unknown command '{}'", verb),
+            CommandParseError::MissingArgument(verb, arg) => write!(f, "'{}' expects argument <{}>", verb, arg),
+            CommandParseError::BadNumber(token) => write!(f, "'{}' is not a valid number", token),
+            CommandParseError::UnknownLedColor(token, choices) => {
+                write!(f, "unknown led color '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownLightSensor(token, choices) => {
+                write!(f, "unknown light sensor '{}', use 1..{}|{}", token, LIGHT_SENSOR_COUNT, Choices(choices))
+            }
+            CommandParseError::UnknownConsoleMode(token, choices) => {
+                write!(f, "unknown console mode '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownQuery(token, choices) => {
+                write!(f, "unknown query '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownSensor(token, choices) => {
+                write!(f, "unknown sensor '{}', use 1..{}|{}", token, LIGHT_SENSOR_COUNT, Choices(choices))
+            }
+            CommandParseError::UnknownLightCommand(token, choices) => {
+                write!(f, "unknown light command '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownTemperatureCommand(token, choices) => {
+                write!(f, "unknown temp command '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownLightResolution(token, choices) => {
+                write!(f, "unknown light resolution '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownI2cCommand(token, choices) => {
+                write!(f, "unknown i2c command '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::UnknownOneWireCommand(token, choices) => {
+                write!(f, "unknown onewire command '{}', use {}", token, Choices(choices))
+            }
+            CommandParseError::OutOfRange(token, min, max) => {
+                write!(f, "'{}' is out of range {}..{}", token, min, max)
+            }
+            CommandParseError::BadAddress(token) => write!(f, "'{}' is not an I2C address 0x08..0x77", token),
+            CommandParseError::BadHex(token) => {
+                write!(f, "'{}' is not a hex string of 1..{} bytes", token, I2C_MAX_LEN)
+            }
+        }
+    }
+}
+
+pub fn str_to_command(msg: &str) -> Result<Commands, CommandParseError<'_>> {
+    registry::dispatch(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{
+        CONSOLE_MODES, I2C_OPS, LED_COLORS, LIGHT_RESOLUTIONS, LIGHT_STATES, ONEWIRE_OPS, QUERIES, SENSOR_NAMES,
+        TEMP_STATES,
+    };
+    use CommandParseError::*;
+
+    #[test]
+    fn led_colors() {
+        let cases = [
+            ("led r on", 1), ("led red on", 1),
+            ("led g on", 2), ("led green on", 2),
+            ("led b on", 3), ("led blue on", 3),
+        ];
+        for (msg, id) in cases {
+            assert_eq!(str_to_command(msg), Ok(Commands::Led(id, LedState::Manual(true))));
+        }
+    }
+
+    #[test]
+    fn led_functions() {
+        assert_eq!(str_to_command("led r on"), Ok(Commands::Led(1, LedState::Manual(true))));
+        assert_eq!(str_to_command("led g off"), Ok(Commands::Led(2, LedState::Manual(false))));
+        assert_eq!(str_to_command("led b 500"), Ok(Commands::Led(3, LedState::Toggle(500))));
+    }
+
+    #[test]
+    fn led_errors() {
+        assert_eq!(str_to_command("led"), Err(MissingArgument("led", "color")));
+        assert_eq!(str_to_command("led r"), Err(MissingArgument("led", "ms")));
+        assert_eq!(str_to_command("led x on"), Err(UnknownLedColor("x", LED_COLORS)));
+        assert_eq!(str_to_command("led r blink"), Err(BadNumber("blink")));
+    }
+
+    #[test]
+    fn status() {
+        assert_eq!(str_to_command("status 10000"), Ok(Commands::UartStatusReport(10000)));
+        assert_eq!(str_to_command("status 0"), Ok(Commands::UartStatusReport(0)));
+        assert_eq!(str_to_command("status"), Err(MissingArgument("status", "ms")));
+        assert_eq!(str_to_command("status abc"), Err(BadNumber("abc")));
+        assert_eq!(str_to_command("status -1"), Err(BadNumber("-1")));
+    }
+
+    #[test]
+    fn light() {
+        use LightSensorCommands::*;
+        assert_eq!(str_to_command("light 1 s"), Ok(Commands::LightSensor(1, SingleMeasurment)));
+        assert_eq!(str_to_command("light 1 single"), Ok(Commands::LightSensor(1, SingleMeasurment)));
+        assert_eq!(str_to_command("light 1 c"), Ok(Commands::LightSensor(1, ContiniousMeasurement)));
+        assert_eq!(str_to_command("light 1 continious"), Ok(Commands::LightSensor(1, ContiniousMeasurement)));
+        assert_eq!(str_to_command("light 1 off"), Ok(Commands::LightSensor(1, Off)));
+        assert_eq!(str_to_command("light"), Err(MissingArgument("light", "sensor")));
+        assert_eq!(str_to_command("light 1"), Err(MissingArgument("light", "state")));
+        assert_eq!(str_to_command("light 1 on"), Err(UnknownLightCommand("on", LIGHT_STATES)));
+    }
+
+    #[test]
+    fn light_sensors() {
+        use LightSensorCommands::*;
+        assert_eq!(str_to_command("light 2 single"), Ok(Commands::LightSensor(2, SingleMeasurment)));
+        assert_eq!(str_to_command("light indoor c"), Ok(Commands::LightSensor(1, ContiniousMeasurement)));
+        assert_eq!(str_to_command("light window off"), Ok(Commands::LightSensor(2, Off)));
+        assert_eq!(str_to_command("light 0 single"), Err(UnknownLightSensor("0", &LIGHT_SENSOR_NAMES)));
+        assert_eq!(str_to_command("light 3 single"), Err(UnknownLightSensor("3", &LIGHT_SENSOR_NAMES)));
+        assert_eq!(str_to_command("light single"), Err(UnknownLightSensor("single", &LIGHT_SENSOR_NAMES)));
+    }
+
+    #[test]
+    fn light_settings() {
+        use LightSensorCommands::*;
+        assert_eq!(str_to_command("light 1 mode hr"), Ok(Commands::LightSensor(1, Resolution(LightResolution::High))));
+        assert_eq!(str_to_command("light 1 mode hr2"), Ok(Commands::LightSensor(1, Resolution(LightResolution::High2))));
+        assert_eq!(str_to_command("light 1 mode lr"), Ok(Commands::LightSensor(1, Resolution(LightResolution::Low))));
+        assert_eq!(str_to_command("light 1 mode"), Err(MissingArgument("light", "value")));
+        assert_eq!(str_to_command("light 1 mode hr3"), Err(UnknownLightResolution("hr3", LIGHT_RESOLUTIONS)));
+
+        assert_eq!(str_to_command("light 1 mtreg 31"), Ok(Commands::LightSensor(1, MeasurementTime(31))));
+        assert_eq!(str_to_command("light 1 mtreg 254"), Ok(Commands::LightSensor(1, MeasurementTime(254))));
+        assert_eq!(str_to_command("light 1 mtreg 30"), Err(OutOfRange("30", 31, 254)));
+        assert_eq!(str_to_command("light 1 mtreg 1000"), Err(OutOfRange("1000", 31, 254)));
+        assert_eq!(str_to_command("light 1 mtreg x"), Err(BadNumber("x")));
+    }
+
+    #[test]
+    fn temperature() {
+        use TemperatureSensorCommands::*;
+        assert_eq!(str_to_command("temp s"), Ok(Commands::TemperatureSensor(SingleMeasurement)));
+        assert_eq!(str_to_command("temp single"), Ok(Commands::TemperatureSensor(SingleMeasurement)));
+        assert_eq!(str_to_command("temp c"), Ok(Commands::TemperatureSensor(ContinuousMeasurement)));
+        assert_eq!(str_to_command("temp continuous"), Ok(Commands::TemperatureSensor(ContinuousMeasurement)));
+        assert_eq!(str_to_command("temp off"), Ok(Commands::TemperatureSensor(Off)));
+        assert_eq!(str_to_command("temp"), Err(MissingArgument("temp", "state")));
+        assert_eq!(str_to_command("temp on"), Err(UnknownTemperatureCommand("on", TEMP_STATES)));
+    }
+
+    #[test]
+    fn temperature_settings() {
+        use TemperatureSensorCommands::*;
+        assert_eq!(str_to_command("temp res 9"), Ok(Commands::TemperatureSensor(Resolution(9))));
+        assert_eq!(str_to_command("temp res 12"), Ok(Commands::TemperatureSensor(Resolution(12))));
+        assert_eq!(str_to_command("temp res"), Err(MissingArgument("temp", "value")));
+        assert_eq!(str_to_command("temp res 8"), Err(OutOfRange("8", 9, 12)));
+        assert_eq!(str_to_command("temp res 13"), Err(OutOfRange("13", 9, 12)));
+
+        assert_eq!(str_to_command("temp alarm -10 30"), Ok(Commands::TemperatureSensor(Alarm(-10, 30))));
+        assert_eq!(str_to_command("temp alarm -55 125"), Ok(Commands::TemperatureSensor(Alarm(-55, 125))));
+        assert_eq!(str_to_command("temp alarm 20 20"), Ok(Commands::TemperatureSensor(Alarm(20, 20))));
+        assert_eq!(str_to_command("temp alarm 20"), Err(MissingArgument("temp", "hi")));
+        assert_eq!(str_to_command("temp alarm -56 20"), Err(OutOfRange("-56", -55, 125)));
+        assert_eq!(str_to_command("temp alarm 20 126"), Err(OutOfRange("126", 20, 125)));
+        assert_eq!(str_to_command("temp alarm 20 10"), Err(OutOfRange("10", 20, 125)));
+        assert_eq!(str_to_command("temp alarm +20 30"), Err(BadNumber("+20")));
+        assert_eq!(str_to_command("temp alarm 2.5 30"), Err(BadNumber("2.5")));
+    }
+
+    #[test]
+    fn queries() {
+        assert_eq!(str_to_command("get led r"), Ok(Commands::Get(Query::Led(1))));
+        assert_eq!(str_to_command("get led blue"), Ok(Commands::Get(Query::Led(3))));
+        assert_eq!(str_to_command("get status"), Ok(Commands::Get(Query::Status)));
+        assert_eq!(str_to_command("get light 1"), Ok(Commands::Get(Query::Light(1))));
+        assert_eq!(str_to_command("get light window"), Ok(Commands::Get(Query::Light(2))));
+        assert_eq!(str_to_command("state"), Ok(Commands::State));
+        assert_eq!(str_to_command("get"), Err(MissingArgument("get", "what")));
+        assert_eq!(str_to_command("get led"), Err(MissingArgument("get", "which")));
+        assert_eq!(str_to_command("get led x"), Err(UnknownLedColor("x", LED_COLORS)));
+        assert_eq!(str_to_command("get temp"), Ok(Commands::Get(Query::Temperature)));
+        assert_eq!(str_to_command("get humidity"), Err(UnknownQuery("humidity", QUERIES)));
+        assert_eq!(str_to_command("get sample indoor"), Ok(Commands::Get(Query::Sample(SensorId::Light(1)))));
+        assert_eq!(str_to_command("get sample temp"), Ok(Commands::Get(Query::Sample(SensorId::Temperature))));
+        assert_eq!(str_to_command("get sample"), Err(MissingArgument("get", "which")));
+    }
+
+    #[test]
+    fn sample() {
+        assert_eq!(str_to_command("sample 1 500"), Ok(Commands::Sample(SensorId::Light(1), 500)));
+        assert_eq!(str_to_command("sample window 150"), Ok(Commands::Sample(SensorId::Light(2), 150)));
+        assert_eq!(str_to_command("sample temp 2000"), Ok(Commands::Sample(SensorId::Temperature, 2000)));
+        assert_eq!(str_to_command("sample"), Err(MissingArgument("sample", "sensor")));
+        assert_eq!(str_to_command("sample temp"), Err(MissingArgument("sample", "ms")));
+        assert_eq!(str_to_command("sample 3 500"), Err(UnknownSensor("3", SENSOR_NAMES)));
+        assert_eq!(str_to_command("sample humidity 500"), Err(UnknownSensor("humidity", SENSOR_NAMES)));
+        assert_eq!(str_to_command("sample temp 9"), Err(OutOfRange("9", 10, 3_600_000)));
+        assert_eq!(str_to_command("sample temp 3600001"), Err(OutOfRange("3600001", 10, 3_600_000)));
+        assert_eq!(str_to_command("sample temp 1s"), Err(BadNumber("1s")));
+    }
+
+    #[test]
+    fn sensor_ids_are_named_like_their_queries() {
+        for (id, query) in [(SensorId::Light(2), Query::Light(2)), (SensorId::Temperature, Query::Temperature)] {
+            let (mut name, mut expected) = (heapless::String::<16>::new(), heapless::String::<16>::new());
+            fmt::write(&mut name, format_args!("{}", id)).unwrap();
+            fmt::write(&mut expected, format_args!("{}", query)).unwrap();
+            assert_eq!(name, expected);
+        }
+    }
+
+    #[test]
+    fn mode() {
+        assert_eq!(str_to_command("mode binary"), Ok(Commands::Mode(ConsoleMode::Binary)));
+        assert_eq!(str_to_command("mode text"), Ok(Commands::Mode(ConsoleMode::Text)));
+        assert_eq!(str_to_command("mode"), Err(MissingArgument("mode", "encoding")));
+        assert_eq!(str_to_command("mode hex"), Err(UnknownConsoleMode("hex", CONSOLE_MODES)));
+    }
+
+    #[test]
+    fn i2c() {
+        assert_eq!(str_to_command("i2c scan"), Ok(Commands::I2cBus(I2cCommands::Scan)));
+        assert_eq!(str_to_command("i2c"), Err(MissingArgument("i2c", "op")));
+        assert_eq!(str_to_command("i2c probe"), Err(UnknownI2cCommand("probe", I2C_OPS)));
+    }
+
+    #[test]
+    fn onewire() {
+        assert_eq!(str_to_command("onewire scan"), Ok(Commands::OneWireBus(OneWireCommands::Scan)));
+        assert_eq!(str_to_command("onewire"), Err(MissingArgument("onewire", "op")));
+        assert_eq!(str_to_command("onewire alarms"), Ok(Commands::OneWireBus(OneWireCommands::AlarmSearch)));
+        assert_eq!(str_to_command("onewire search"), Err(UnknownOneWireCommand("search", ONEWIRE_OPS)));
+    }
+
+    #[test]
+    fn i2c_transfers() {
+        let bytes = I2cBytes::new(&[0x01, 0x10]).unwrap();
+        assert_eq!(str_to_command("i2c write 0x23 0110"), Ok(Commands::I2cBus(I2cCommands::Write(0x23, bytes))));
+        assert_eq!(str_to_command("i2c write 5C 0110"), Ok(Commands::I2cBus(I2cCommands::Write(0x5C, bytes))));
+        assert_eq!(str_to_command("i2c read 0x23 2"), Ok(Commands::I2cBus(I2cCommands::Read(0x23, 2))));
+        assert_eq!(
+            str_to_command("i2c wr 0x23 0110 16"),
+            Ok(Commands::I2cBus(I2cCommands::WriteRead(0x23, bytes, 16)))
+        );
+
+        assert_eq!(str_to_command("i2c read"), Err(MissingArgument("i2c", "addr")));
+        assert_eq!(str_to_command("i2c read 0x23"), Err(MissingArgument("i2c", "bytes")));
+        assert_eq!(str_to_command("i2c wr 0x23 10"), Err(MissingArgument("i2c", "len")));
+        assert_eq!(str_to_command("i2c read 0x07 1"), Err(BadAddress("0x07")));
+        assert_eq!(str_to_command("i2c read 0x78 1"), Err(BadAddress("0x78")));
+        assert_eq!(str_to_command("i2c read 0xg1 1"), Err(BadAddress("0xg1")));
+        assert_eq!(str_to_command("i2c read 0x23 0"), Err(OutOfRange("0", 1, 16)));
+        assert_eq!(str_to_command("i2c read 0x23 17"), Err(OutOfRange("17", 1, 16)));
+        assert_eq!(str_to_command("i2c write 0x23 011"), Err(BadHex("011")));
+        assert_eq!(str_to_command("i2c write 0x23 0x10"), Err(BadHex("0x10")));
+        assert_eq!(str_to_command("i2c write 0x23 +1"), Err(BadHex("+1")));
+        assert_eq!(str_to_command("i2c write 0x23 000102030405060708090a0b0c0d0e0f10"), Err(BadHex("000102030405060708090a0b0c0d0e0f10")));
+    }
+
+    #[test]
+    fn whitespace_is_tolerated() {
+        assert_eq!(str_to_command("  led   r\ton \r\n"), Ok(Commands::Led(1, LedState::Manual(true))));
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        let cases = [
+            Commands::UartStatusReport(250),
+            Commands::Led(1, LedState::Manual(true)),
+            Commands::Led(2, LedState::Manual(false)),
+            Commands::Led(3, LedState::Toggle(100)),
+            Commands::LightSensor(2, LightSensorCommands::Off),
+            Commands::LightSensor(2, LightSensorCommands::SingleMeasurment),
+            Commands::LightSensor(2, LightSensorCommands::ContiniousMeasurement),
+            Commands::LightSensor(2, LightSensorCommands::Resolution(LightResolution::High2)),
+            Commands::LightSensor(2, LightSensorCommands::MeasurementTime(138)),
+            Commands::Get(Query::Led(2)),
+            Commands::Get(Query::Status),
+            Commands::Get(Query::Light(2)),
+            Commands::Get(Query::Temperature),
+            Commands::Get(Query::Sample(SensorId::Light(2))),
+            Commands::Get(Query::Sample(SensorId::Temperature)),
+            Commands::TemperatureSensor(TemperatureSensorCommands::Off),
+            Commands::TemperatureSensor(TemperatureSensorCommands::SingleMeasurement),
+            Commands::TemperatureSensor(TemperatureSensorCommands::ContinuousMeasurement),
+            Commands::TemperatureSensor(TemperatureSensorCommands::Resolution(9)),
+            Commands::TemperatureSensor(TemperatureSensorCommands::Alarm(-55, -10)),
+            Commands::State,
+            Commands::Mode(ConsoleMode::Text),
+            Commands::Mode(ConsoleMode::Binary),
+            Commands::I2cBus(I2cCommands::Scan),
+            Commands::I2cBus(I2cCommands::Write(0x23, I2cBytes::new(&[0x01]).unwrap())),
+            Commands::I2cBus(I2cCommands::Read(0x5C, 16)),
+            Commands::I2cBus(I2cCommands::WriteRead(0x08, I2cBytes::new(&[0xab, 0xcd]).unwrap(), 2)),
+            Commands::OneWireBus(OneWireCommands::Scan),
+            Commands::OneWireBus(OneWireCommands::AlarmSearch),
+            Commands::Sample(SensorId::Light(1), 10),
+            Commands::Sample(SensorId::Temperature, 3_600_000),
+            Commands::Help(None),
+            Commands::Help(Some(0)),
+        ];
+        for cmd in cases {
+            let mut buf = heapless::String::<32>::new();
+            fmt::write(&mut buf, format_args!("{}", cmd)).unwrap();
+            assert_eq!(str_to_command(&buf), Ok(cmd));
+        }
+    }
+
+    #[test]
+    fn unknown_verbs() {
+        assert_eq!(str_to_command(""), Err(UnknownCommand("")));
+        assert_eq!(str_to_command("humidity"), Err(UnknownCommand("humidity")));
+        assert_eq!(str_to_command("ledr on"), Err(UnknownCommand("ledr")));
+    }
+}
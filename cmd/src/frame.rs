@@ -0,0 +1,340 @@
+//! The binary protocol of the developer console, it is enabled by the `mode binary` command.
+//!
+//! A [Frame] holds a sequence number and a payload, the [Commands] of the host are sent as
+//! [Request] and answered by [Reply] frames that carry the same sequence number. On the wire a
+//! frame is encoded as follows:
+//!
+//! 1. the frame is serialized with [postcard]
+//! 2. a CRC-16 of the serialized bytes is appended in little endian, see [crc16]
+//! 3. the result is COBS encoded, such that it contains no zero bytes
+//! 4. a zero byte delimits the frame
+//!
+//! Noise on the line is detected by the CRC and a receiver resynchronizes on the next zero byte.
+//! The sequence number 0 is reserved for frames that do not answer a request, the host uses
+//! [next_seq] to count its requests.
+
+use core::fmt;
+
+use heapless::Vec;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::cmd::Commands;
+use crate::response::Response;
+
+/// The maximum size of an encoded frame including the delimiter
+pub const MAX_FRAME_LEN: usize = 128;
+
+/// The byte that delimits frames on the wire
+pub const DELIMITER: u8 = 0x00;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame<T> {
+    pub seq: u16,
+    pub payload: T,
+}
+
+/// A command sent by the host
+pub type Request = Frame<Commands>;
+
+/// A response to the request with the same sequence number
+pub type Reply = Frame<Response>;
+
+impl<T> Frame<T> {
+    pub fn new(seq: u16, payload: T) -> Self {
+        Frame { seq, payload }
+    }
+}
+
+/// Gives the sequence number following the given one, the reserved 0 is skipped
+pub fn next_seq(seq: u16) -> u16 {
+    match seq.wrapping_add(1) {
+        0 => 1,
+        next => next,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// the frame does not fit into [MAX_FRAME_LEN] bytes
+    TooLong,
+
+    /// the COBS encoding of the received frame is invalid
+    Cobs,
+
+    /// the CRC of the received frame does not match
+    Crc,
+
+    /// postcard failed to serialize or deserialize the frame
+    Encoding,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::TooLong => f.write_str("frame too long"),
+            FrameError::Cobs => f.write_str("invalid COBS encoding"),
+            FrameError::Crc => f.write_str("CRC mismatch"),
+            FrameError::Encoding => f.write_str("invalid frame content"),
+        }
+    }
+}
+
+/// The CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF)
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Encodes the frame into `out` including the delimiter, gives the number of bytes written
+pub fn encode<T: Serialize>(frame: &Frame<T>, out: &mut [u8]) -> Result<usize, FrameError> {
+    let mut raw = [0u8; MAX_FRAME_LEN];
+    let len = postcard::to_slice(frame, &mut raw[..MAX_FRAME_LEN - 2])
+        .map_err(|_| FrameError::Encoding)?
+        .len();
+    let crc = crc16(&raw[..len]);
+    raw[len..len + 2].copy_from_slice(&crc.to_le_bytes());
+
+    let encoded_len = cobs::try_encode(&raw[..len + 2], out).map_err(|_| FrameError::TooLong)?;
+    let delimiter = out.get_mut(encoded_len).ok_or(FrameError::TooLong)?;
+    *delimiter = DELIMITER;
+    Ok(encoded_len + 1)
+}
+
+/// Decodes a received frame without delimiter, the buffer is used as scratch space
+pub fn decode<T: DeserializeOwned>(encoded: &mut [u8]) -> Result<Frame<T>, FrameError> {
+    let len = cobs::decode_in_place(encoded).map_err(|_| FrameError::Cobs)?;
+    if len < 2 {
+        return Err(FrameError::Crc);
+    }
+
+    let (raw, crc) = encoded[..len].split_at(len - 2);
+    if crc16(raw).to_le_bytes() != crc {
+        return Err(FrameError::Crc);
+    }
+    postcard::from_bytes(raw).map_err(|_| FrameError::Encoding)
+}
+
+/// Collects received bytes until a delimiter completes a frame
+pub struct FrameReader {
+    buf: Vec<u8, MAX_FRAME_LEN>,
+    overflow: bool,
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameReader {
+    pub const fn new() -> Self {
+        FrameReader {
+            buf: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Processes one received byte and gives the decoded frame once it is delimited
+    pub fn feed<T: DeserializeOwned>(&mut self, byte: u8) -> Option<Result<Frame<T>, FrameError>> {
+        if byte != DELIMITER {
+            if self.buf.push(byte).is_err() {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        let result = if self.overflow {
+            Some(Err(FrameError::TooLong))
+        } else if self.buf.is_empty() {
+            // consecutive delimiters are used to resynchronize
+            None
+        } else {
+            Some(decode(&mut self.buf))
+        };
+        self.buf.clear();
+        self.overflow = false;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{
+        ConsoleMode, I2cBytes, I2cCommands, LightResolution, LightSensorCommands, OneWireCommands, Query, SensorId,
+        TemperatureSensorCommands, I2C_MAX_LEN,
+    };
+    use crate::led::LedState;
+    use nucleo_types::onewire::{PowerMode, Rom};
+    use nucleo_types::units::{Celsius, Lux};
+
+    /// the index of the variant, the match makes this test fail to compile on a new variant
+    fn variant_index(cmd: &Commands) -> usize {
+        match cmd {
+            Commands::UartStatusReport(_) => 0,
+            Commands::Led(_, _) => 1,
+            Commands::LightSensor(_, _) => 2,
+            Commands::TemperatureSensor(_) => 3,
+            Commands::Get(_) => 4,
+            Commands::State => 5,
+            Commands::Mode(_) => 6,
+            Commands::Help(_) => 7,
+            Commands::I2cBus(_) => 8,
+            Commands::OneWireBus(_) => 9,
+            Commands::Sample(_, _) => 10,
+        }
+    }
+    const NUM_VARIANTS: usize = 11;
+
+    fn all_commands() -> [Commands; 29] {
+        [
+            Commands::UartStatusReport(0),
+            Commands::UartStatusReport(u32::MAX),
+            Commands::Led(1, LedState::Manual(true)),
+            Commands::Led(2, LedState::Manual(false)),
+            Commands::Led(3, LedState::Toggle(500)),
+            Commands::LightSensor(1, LightSensorCommands::Off),
+            Commands::LightSensor(1, LightSensorCommands::SingleMeasurment),
+            Commands::LightSensor(1, LightSensorCommands::ContiniousMeasurement),
+            Commands::LightSensor(1, LightSensorCommands::Resolution(LightResolution::Low)),
+            Commands::LightSensor(1, LightSensorCommands::MeasurementTime(254)),
+            Commands::TemperatureSensor(TemperatureSensorCommands::Off),
+            Commands::TemperatureSensor(TemperatureSensorCommands::SingleMeasurement),
+            Commands::TemperatureSensor(TemperatureSensorCommands::ContinuousMeasurement),
+            Commands::TemperatureSensor(TemperatureSensorCommands::Resolution(12)),
+            Commands::TemperatureSensor(TemperatureSensorCommands::Alarm(i8::MIN, i8::MAX)),
+            Commands::Get(Query::Led(1)),
+            Commands::Get(Query::Status),
+            Commands::Get(Query::Light(2)),
+            Commands::Get(Query::Temperature),
+            Commands::Get(Query::Sample(SensorId::Temperature)),
+            Commands::State,
+            Commands::Mode(ConsoleMode::Text),
+            Commands::Mode(ConsoleMode::Binary),
+            Commands::I2cBus(I2cCommands::Scan),
+            Commands::OneWireBus(OneWireCommands::Scan),
+            Commands::OneWireBus(OneWireCommands::AlarmSearch),
+            Commands::Sample(SensorId::Light(2), u32::MAX),
+            Commands::Help(None),
+            Commands::Help(Some(2)),
+        ]
+    }
+
+    fn roundtrip<T: Serialize + DeserializeOwned>(frame: &Frame<T>) -> Frame<T> {
+        let mut wire = [0u8; MAX_FRAME_LEN];
+        let len = encode(frame, &mut wire).unwrap();
+        assert_eq!(wire[len - 1], DELIMITER);
+        assert!(!wire[..len - 1].contains(&DELIMITER));
+        decode(&mut wire[..len - 1]).unwrap()
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(b""), 0xFFFF);
+    }
+
+    #[test]
+    fn every_command_roundtrips() {
+        let mut seen = [false; NUM_VARIANTS];
+        let mut seq = 0;
+        for cmd in all_commands() {
+            seq = next_seq(seq);
+            seen[variant_index(&cmd)] = true;
+            let request = Request::new(seq, cmd);
+            assert_eq!(roundtrip(&request), request);
+        }
+        assert!(seen.iter().all(|seen| *seen), "every variant has to be tested");
+    }
+
+    #[test]
+    fn responses_roundtrip() {
+        let responses = [
+            Response::Ok(Commands::State),
+            Response::error(&FrameError::Crc),
+            Response::LightValue(1, Lux::from_millilux(0)),
+            Response::LightValue(2, Lux::from_millilux(u32::MAX)),
+            Response::TemperatureValue(Celsius::from_millidegrees(-55_000)),
+            Response::State(Commands::UartStatusReport(10000)),
+            Response::Usage(1),
+            Response::ArgUsage(0, 1),
+            Response::ScanHeader,
+            Response::ScanRow(7, u16::MAX),
+            Response::Device(0x23),
+            Response::I2cData(0x77, I2cBytes::new(&[0xFF; I2C_MAX_LEN]).unwrap()),
+            Response::OneWireDevice(Rom([0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x34]), PowerMode::Parasitic),
+        ];
+        for response in responses {
+            let reply = Reply::new(7, response);
+            assert_eq!(roundtrip(&reply), reply);
+        }
+
+        let reason = "0123456789012345678901234567890123456789012345678901234567890123";
+        let longest = Reply::new(u16::MAX, Response::error(&reason));
+        assert_eq!(roundtrip(&longest), longest);
+    }
+
+    #[test]
+    fn sequence_skips_zero() {
+        assert_eq!(next_seq(0), 1);
+        assert_eq!(next_seq(41), 42);
+        assert_eq!(next_seq(u16::MAX), 1);
+    }
+
+    #[test]
+    fn corruption_is_detected() {
+        let mut wire = [0u8; MAX_FRAME_LEN];
+        let len = encode(&Request::new(1, Commands::Led(1, LedState::Toggle(100))), &mut wire).unwrap();
+        for idx in 1..len - 1 {
+            let mut corrupted = wire;
+            corrupted[idx] ^= 0x10;
+            if corrupted[idx] == DELIMITER {
+                continue;
+            }
+            let result: Result<Request, _> = decode(&mut corrupted[..len - 1]);
+            assert!(result.is_err(), "flipped bit in byte {} not detected", idx);
+        }
+    }
+
+    #[test]
+    fn reader_resynchronizes() {
+        let mut wire = [0u8; MAX_FRAME_LEN];
+        let request = Request::new(3, Commands::Get(Query::Status));
+        let len = encode(&request, &mut wire).unwrap();
+
+        let mut reader = FrameReader::new();
+        let mut results: Vec<Result<Request, FrameError>, 4> = Vec::new();
+        // noise before, a split frame and double delimiters
+        let chunks: [&[u8]; 4] = [b"\x13\x37\x00\x00", &wire[..2], &wire[2..len], b"\x00"];
+        for chunk in chunks {
+            for &byte in chunk {
+                if let Some(result) = reader.feed(byte) {
+                    results.push(result).unwrap();
+                }
+            }
+        }
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_err());
+        assert_eq!(results[1], Ok(request));
+    }
+
+    #[test]
+    fn reader_rejects_overlong_frames() {
+        let mut reader = FrameReader::new();
+        for _ in 0..MAX_FRAME_LEN + 1 {
+            assert_eq!(reader.feed::<Request>(0x42), None);
+        }
+        assert_eq!(reader.feed::<Request>(DELIMITER), Some(Err(FrameError::TooLong)));
+    }
+}
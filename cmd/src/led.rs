@@ -0,0 +1,84 @@
+//! The hardware independent model of an LED state as it is used by commands
+//!
+//! The LED control is simplistic and contans the states off/on/toggle(ms) as encoded in [LedState].
+//! The [Display](core::fmt::Display) implementation is the inverse of [str_to_led_state].
+
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedState {
+    /// the led is either on or off
+    Manual(bool),
+
+    /// the led toogles every specified amount of ms
+    Toggle(u32),
+}
+
+impl Default for LedState {
+    fn default() -> Self {
+        LedState::Manual(false)
+    }
+}
+
+impl fmt::Display for LedState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedState::Manual(true) => f.write_str("on"),
+            LedState::Manual(false) => f.write_str("off"),
+            LedState::Toggle(ms) => write!(f, "{}", ms),
+        }
+    }
+}
+
+pub fn str_to_led_state(txt: &str) -> Option<LedState> {
+    match txt {
+        "off" => Some(LedState::Manual(false)),
+        "on" => Some(LedState::Manual(true)),
+        other => {
+            match other.parse() {
+                Ok(ms) => Some(LedState::Toggle(ms)),
+                Err(_) => None 
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn on_and_off() {
+        assert_eq!(str_to_led_state("on"), Some(LedState::Manual(true)));
+        assert_eq!(str_to_led_state("off"), Some(LedState::Manual(false)));
+    }
+
+    #[test]
+    fn toggle_interval() {
+        assert_eq!(str_to_led_state("250"), Some(LedState::Toggle(250)));
+        assert_eq!(str_to_led_state("0"), Some(LedState::Toggle(0)));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(str_to_led_state(""), None);
+        assert_eq!(str_to_led_state("-5"), None);
+        assert_eq!(str_to_led_state("blink"), None);
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for state in [LedState::Manual(true), LedState::Manual(false), LedState::Toggle(1000)] {
+            let mut buf = heapless::String::<16>::new();
+            core::fmt::write(&mut buf, format_args!("{}", state)).unwrap();
+            assert_eq!(str_to_led_state(&buf), Some(state));
+        }
+    }
+
+    #[test]
+    fn default_is_off() {
+        assert_eq!(LedState::default(), LedState::Manual(false));
+    }
+}
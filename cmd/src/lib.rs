@@ -0,0 +1,34 @@
+#![no_std]
+
+//! This crate contains the hardware independent command model of the sensor platform
+//! and the parser for the text based developer console.
+//!
+//! It is used by the `nucleo_f767zi` firmware and can be tested on the host:
+//!
+//! - [x] LED states and their textual representation
+//! - [x] Command model and parsing with typed errors
+//! - [x] Table driven command registry that generates the `help` output
+//! - [x] Line editing for the serial console with echo and history
+//! - [x] COBS framed binary protocol with CRC-16 and postcard encoded messages
+//! - [x] Responses and acknowledgements sent back over the console
+//! - [x] Status report that is formatted by the firmware and parsed by the host tool
+
+pub mod cmd;
+pub mod frame;
+pub mod led;
+pub mod line;
+pub mod registry;
+pub mod report;
+pub mod response;
+
+pub use cmd::{
+    str_to_command, CommandParseError, Commands, ConsoleMode, I2cBytes, I2cCommands, LightResolution, LightSensorCommands, LightSensorState,
+    OneWireCommands, Query, SensorId, TemperatureSensorCommands, TemperatureSensorState,
+};
+pub use led::{str_to_led_state, LedState};
+pub use line::LineEditor;
+pub use report::{LightReport, StatusReport, TemperatureReport};
+
+pub use nucleo_types::error::{BusFault, OneWireFault};
+pub use nucleo_types::units::{Celsius, Lux};
+pub use response::Response;
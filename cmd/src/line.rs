@@ -0,0 +1,336 @@
+//! A line discipline for the serial developer console, see [LineEditor].
+//!
+//! The editor is fed byte by byte from the UART and assembles complete lines independent of how
+//! the bytes are split into DMA transfers. It supports:
+//!
+//! - local echo of printable ASCII characters
+//! - backspace (`^H`) and delete (`DEL`, as sent by most terminals on backspace)
+//! - CR, LF and CRLF line termination
+//! - a command history that is browsed with the up/down arrow keys
+//! - Ctrl-C to discard the current line
+//!
+//! The echo is collected in an internal buffer that has to be written back to the terminal by
+//! the caller, see [LineEditor::echo] and [LineEditor::clear_echo].
+
+use heapless::{Deque, String, Vec};
+
+const CTRL_C: u8 = 0x03;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7F;
+const ESCAPE: u8 = 0x1B;
+const BELL: u8 = 0x07;
+
+/// The capacity of the echo buffer, it can hold the redraw of a full line
+const ECHO_CAPACITY: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    Normal,
+    Escape,
+    ControlSequence,
+}
+
+/// Assembles lines of at most `N` characters and remembers the last `H` lines
+pub struct LineEditor<const N: usize, const H: usize> {
+    line: String<N>,
+    history: Deque<String<N>, H>,
+    history_pos: Option<usize>,
+    escape: EscapeState,
+    last_was_cr: bool,
+    echo: Vec<u8, ECHO_CAPACITY>,
+}
+
+impl<const N: usize, const H: usize> Default for LineEditor<N, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const H: usize> LineEditor<N, H> {
+    pub const fn new() -> Self {
+        LineEditor {
+            line: String::new(),
+            history: Deque::new(),
+            history_pos: None,
+            escape: EscapeState::Normal,
+            last_was_cr: false,
+            echo: Vec::new(),
+        }
+    }
+
+    /// Processes one byte received from the terminal and gives the line once it is terminated
+    ///
+    /// Empty lines are returned too, such that the caller may react on a bare return.
+    pub fn feed(&mut self, byte: u8) -> Option<String<N>> {
+        let after_cr = self.last_was_cr;
+        self.last_was_cr = false;
+
+        match self.escape {
+            EscapeState::Escape => {
+                self.escape = match byte {
+                    b'[' | b'O' => EscapeState::ControlSequence,
+                    _ => EscapeState::Normal,
+                };
+                return None;
+            }
+            EscapeState::ControlSequence => {
+                // parameter bytes are skipped, the final byte ends the sequence
+                if (0x40..=0x7E).contains(&byte) {
+                    self.escape = EscapeState::Normal;
+                    match byte {
+                        b'A' => self.history_up(),
+                        b'B' => self.history_down(),
+                        _ => {}
+                    }
+                }
+                return None;
+            }
+            EscapeState::Normal => {}
+        }
+
+        match byte {
+            b'\r' => {
+                self.last_was_cr = true;
+                Some(self.finish_line())
+            }
+            b'\n' if after_cr => None,
+            b'\n' => Some(self.finish_line()),
+            BACKSPACE | DELETE => {
+                if self.line.pop().is_some() {
+                    self.push_echo(b"\x08 \x08");
+                }
+                None
+            }
+            CTRL_C => {
+                self.line.clear();
+                self.history_pos = None;
+                self.push_echo(b"^C\r\n");
+                None
+            }
+            ESCAPE => {
+                self.escape = EscapeState::Escape;
+                None
+            }
+            0x20..=0x7E => {
+                if self.line.push(byte as char).is_ok() {
+                    self.push_echo(&[byte]);
+                } else {
+                    self.push_echo(&[BELL]);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// The line that is currently edited
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// The bytes that have to be written back to the terminal
+    pub fn echo(&self) -> &[u8] {
+        &self.echo
+    }
+
+    /// Has to be called once the echo has been written to the terminal
+    pub fn clear_echo(&mut self) {
+        self.echo.clear();
+    }
+
+    /// The remembered lines, the most recent first
+    pub fn history(&self) -> impl Iterator<Item = &str> {
+        self.history.iter().map(|line| line.as_str())
+    }
+
+    fn finish_line(&mut self) -> String<N> {
+        self.push_echo(b"\r\n");
+        self.history_pos = None;
+
+        let line = core::mem::take(&mut self.line);
+        let is_repetition = self.history.front().map(|last| *last == line).unwrap_or(false);
+        if H > 0 && !line.is_empty() && !is_repetition {
+            if self.history.is_full() {
+                self.history.pop_back();
+            }
+            // cannot fail as there is space now
+            let _ = self.history.push_front(line.clone());
+        }
+        line
+    }
+
+    fn history_up(&mut self) {
+        let next = match self.history_pos {
+            None => 0,
+            Some(pos) => pos + 1,
+        };
+        if next < self.history.len() {
+            self.history_pos = Some(next);
+            self.recall();
+        } else {
+            self.push_echo(&[BELL]);
+        }
+    }
+
+    fn history_down(&mut self) {
+        match self.history_pos {
+            None => self.push_echo(&[BELL]),
+            Some(0) => {
+                self.history_pos = None;
+                self.line.clear();
+                self.redraw();
+            }
+            Some(pos) => {
+                self.history_pos = Some(pos - 1);
+                self.recall();
+            }
+        }
+    }
+
+    fn recall(&mut self) {
+        if let Some(entry) = self.history_pos.and_then(|pos| self.history.iter().nth(pos)) {
+            self.line = entry.clone();
+        }
+        self.redraw();
+    }
+
+    fn redraw(&mut self) {
+        // return to the line start and erase it before printing the line again
+        self.push_echo(b"\r\x1b[K");
+        let line = self.line.clone();
+        self.push_echo(line.as_bytes());
+    }
+
+    fn push_echo(&mut self, bytes: &[u8]) {
+        // the echo is best effort, if the caller does not flush it in time it is cut off
+        let free = self.echo.capacity() - self.echo.len();
+        let _ = self.echo.extend_from_slice(&bytes[..bytes.len().min(free)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Editor = LineEditor<16, 3>;
+
+    /// feeds all bytes and collects the completed lines
+    fn feed_all(editor: &mut Editor, bytes: &[u8], lines: &mut Vec<String<16>, 8>) {
+        for &byte in bytes {
+            if let Some(line) = editor.feed(byte) {
+                lines.push(line).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn assembles_across_chunks() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"l", &mut lines);
+        feed_all(&mut editor, b"ed r", &mut lines);
+        assert!(lines.is_empty());
+        feed_all(&mut editor, b" on\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["led r on"]);
+    }
+
+    #[test]
+    fn echo() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"hi\r", &mut lines);
+        assert_eq!(editor.echo(), b"hi\r\n");
+        editor.clear_echo();
+        assert_eq!(editor.echo(), b"");
+    }
+
+    #[test]
+    fn line_terminations() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"a\rb\nc\r\nd\n\re\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["a", "b", "c", "d", "", "e"]);
+    }
+
+    #[test]
+    fn crlf_split_over_chunks() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"a\r", &mut lines);
+        feed_all(&mut editor, b"\nb\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["a", "b"]);
+    }
+
+    #[test]
+    fn backspace_and_delete() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"leds\x08 x\x7Fr on\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["led r on"]);
+
+        editor.clear_echo();
+        feed_all(&mut editor, b"\x08", &mut lines);
+        assert_eq!(editor.echo(), b"", "nothing to erase in an empty line");
+    }
+
+    #[test]
+    fn ctrl_c_discards_line() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"led r\x03status 5\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["status 5"]);
+        assert!(editor.echo().starts_with(b"led r^C\r\n"));
+    }
+
+    #[test]
+    fn overlong_lines_ring_the_bell() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"0123456789abcdefXYZ\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["0123456789abcdef"]);
+        assert_eq!(editor.echo().iter().filter(|&&b| b == BELL).count(), 3);
+    }
+
+    #[test]
+    fn history_up_and_down() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"one\rtwo\r", &mut lines);
+
+        editor.clear_echo();
+        feed_all(&mut editor, b"\x1b[A", &mut lines);
+        assert_eq!(editor.line(), "two");
+        assert_eq!(editor.echo(), b"\r\x1b[Ktwo");
+
+        feed_all(&mut editor, b"\x1b[A\x1b[A", &mut lines);
+        assert_eq!(editor.line(), "one", "stays at the oldest entry");
+
+        feed_all(&mut editor, b"\x1b[B", &mut lines);
+        assert_eq!(editor.line(), "two");
+        feed_all(&mut editor, b"\x1b[B", &mut lines);
+        assert_eq!(editor.line(), "");
+
+        feed_all(&mut editor, b"\x1bOA\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["one", "two", "two"]);
+    }
+
+    #[test]
+    fn history_is_bounded_and_skips_repetitions() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"a\rb\rb\r\rc\rd\r", &mut lines);
+        let mut history = editor.history();
+        assert_eq!(history.next(), Some("d"));
+        assert_eq!(history.next(), Some("c"));
+        assert_eq!(history.next(), Some("b"));
+        assert_eq!(history.next(), None);
+    }
+
+    #[test]
+    fn other_escape_sequences_are_ignored() {
+        let mut editor = Editor::new();
+        let mut lines = Vec::new();
+        feed_all(&mut editor, b"ab\x1b[3~\x1b[C\x1b[1;5Dc\r", &mut lines);
+        assert_eq!(lines.as_slice(), ["abc"]);
+    }
+}
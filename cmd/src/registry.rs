@@ -0,0 +1,680 @@
+//! The table driven command registry, see [COMMANDS].
+//!
+//! Each [CommandSpec] declares the name, aliases, argument schema and help text of a command
+//! together with the function that parses its arguments. [str_to_command](crate::str_to_command)
+//! dispatches through the table and the usage printed by `help` is generated from it, such that
+//! the grammar of the console has a single source of truth.
+
+use core::fmt;
+use core::ops::RangeInclusive;
+use core::str::SplitWhitespace;
+
+use crate::cmd::{
+    CommandParseError, Commands, ConsoleMode, I2cBytes, I2cCommands, LightResolution, LightSensorCommands, OneWireCommands, Query,
+    SensorId, TemperatureSensorCommands, LIGHT_SENSOR_NAMES, MTREG_RANGE, I2C_MAX_LEN, SAMPLE_PERIOD_RANGE, TEMP_RANGE,
+    TEMP_RESOLUTION_RANGE,
+};
+use crate::led::str_to_led_state;
+use nucleo_types::scan::SCAN_RANGE;
+
+/// The kind of token an argument accepts, used to generate the usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// one of the given keywords
+    Choice(&'static [&'static str]),
+
+    /// an unsigned number, rendered as `<name>`
+    Number,
+
+    /// one of the given keywords or an unsigned number
+    ChoiceOrNumber(&'static [&'static str]),
+
+    /// the name of another command in the registry
+    Command,
+}
+
+/// The keywords of the arguments, shared by the schema and the errors of the parser
+pub const LED_COLORS: &[&str] = &["r", "g", "b"];
+pub const LIGHT_STATES: &[&str] = &["s", "c", "off", "mode", "mtreg"];
+pub const LIGHT_RESOLUTIONS: &[&str] = &["hr", "hr2", "lr"];
+pub const TEMP_STATES: &[&str] = &["s", "c", "off", "res", "alarm"];
+pub const QUERIES: &[&str] = &["led", "status", "light", "temp", "sample"];
+pub const SENSOR_NAMES: &[&str] = &["indoor", "window", "temp"];
+pub const CONSOLE_MODES: &[&str] = &["text", "binary"];
+pub const I2C_OPS: &[&str] = &["scan", "write", "read", "wr"];
+pub const ONEWIRE_OPS: &[&str] = &["scan", "alarms"];
+
+/// Renders keywords in the usage form, e.g. `r|g|b`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Choices(pub &'static [&'static str]);
+
+impl fmt::Display for Choices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_choices(f, self.0)
+    }
+}
+
+/// Describes one positional argument of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+    pub help: &'static str,
+}
+
+/// The parse function of a command, it reads the arguments following the verb
+pub type ParseFn = for<'a> fn(&mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>>;
+
+/// Describes a command of the console and how to parse it
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub args: &'static [ArgSpec],
+    pub help: &'static str,
+    pub parse: ParseFn,
+}
+
+/// The arguments of a command in the message, handed out in the order of the argument schema
+pub struct Arguments<'a> {
+    verb: &'a str,
+    spec: &'static CommandSpec,
+    split: SplitWhitespace<'a>,
+    pos: usize,
+}
+
+impl<'a> Arguments<'a> {
+    /// Gives the next argument, or a [CommandParseError::MissingArgument] named after the schema
+    pub fn required(&mut self) -> Result<&'a str, CommandParseError<'a>> {
+        let name = self.spec.args.get(self.pos).map(|arg| arg.name).unwrap_or("?");
+        self.pos += 1;
+        self.split.next().ok_or(CommandParseError::MissingArgument(self.verb, name))
+    }
+
+    /// Gives the next argument if it is present, used for optional arguments
+    pub fn optional(&mut self) -> Option<&'a str> {
+        self.pos += 1;
+        self.split.next()
+    }
+
+    /// Gives the next argument parsed as number
+    pub fn number(&mut self) -> Result<u32, CommandParseError<'a>> {
+        let token = self.required()?;
+        token.parse().map_err(|_| CommandParseError::BadNumber(token))
+    }
+
+    /// Gives the next argument parsed as number that has to be in the given range
+    pub fn number_in(&mut self, range: RangeInclusive<u32>) -> Result<u32, CommandParseError<'a>> {
+        let token = self.required()?;
+        let number = token.parse().map_err(|_| CommandParseError::BadNumber(token))?;
+        if range.contains(&number) {
+            Ok(number)
+        } else {
+            Err(CommandParseError::OutOfRange(token, *range.start() as i64, *range.end() as i64))
+        }
+    }
+
+    /// Gives the next argument parsed as number with an optional minus sign that has to be in the given range
+    pub fn signed_in(&mut self, range: RangeInclusive<i32>) -> Result<i32, CommandParseError<'a>> {
+        let token = self.required()?;
+        let number = token
+            .parse()
+            .ok()
+            .filter(|_| !token.starts_with('+'))
+            .ok_or(CommandParseError::BadNumber(token))?;
+        if range.contains(&number) {
+            Ok(number)
+        } else {
+            Err(CommandParseError::OutOfRange(token, *range.start() as i64, *range.end() as i64))
+        }
+    }
+}
+
+const LED_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "color",
+        kind: ArgKind::Choice(LED_COLORS),
+        optional: false,
+        help: "red, green or blue user LED, the long names may be used too",
+    },
+    ArgSpec {
+        name: "ms",
+        kind: ArgKind::ChoiceOrNumber(&["on", "off"]),
+        optional: false,
+        help: "turns the LED on or off, or toggles it every given ms",
+    },
+];
+
+const STATUS_ARGS: &[ArgSpec] = &[ArgSpec {
+    name: "ms",
+    kind: ArgKind::Number,
+    optional: false,
+    help: "the ms between each status report, 0 disables the report",
+}];
+
+const LIGHT_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "sensor",
+        kind: ArgKind::ChoiceOrNumber(&LIGHT_SENSOR_NAMES),
+        optional: false,
+        help: "the sensor by its id or name, 1 is the BH1750FVI at 0x23 and 2 the one at 0x5C",
+    },
+    ArgSpec {
+        name: "state",
+        kind: ArgKind::Choice(LIGHT_STATES),
+        optional: false,
+        help: "single or continious measurement, long names may be used too, off shuts the sensor down",
+    },
+    ArgSpec {
+        name: "value",
+        kind: ArgKind::ChoiceOrNumber(LIGHT_RESOLUTIONS),
+        optional: true,
+        help: "the resolution of mode or the measurement time 31..254 of mtreg",
+    },
+];
+
+const TEMP_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "state",
+        kind: ArgKind::Choice(TEMP_STATES),
+        optional: false,
+        help: "single or continuous measurement, long names may be used too, off stops the measurements",
+    },
+    ArgSpec {
+        name: "value",
+        kind: ArgKind::Number,
+        optional: true,
+        help: "the resolution 9..12 bits of res or the lower alarm limit -55..125 °C of alarm",
+    },
+    ArgSpec {
+        name: "hi",
+        kind: ArgKind::Number,
+        optional: true,
+        help: "the upper alarm limit of alarm, at least the lower one",
+    },
+];
+
+const GET_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "what",
+        kind: ArgKind::Choice(QUERIES),
+        optional: false,
+        help: "the part of the state to read back",
+    },
+    ArgSpec {
+        name: "which",
+        kind: ArgKind::Choice(&["r", "g", "b", "1", "2", "temp"]),
+        optional: true,
+        help: "the LED by color, the light sensor by id or the sensor of sample, the names may be used too",
+    },
+];
+
+const SAMPLE_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "sensor",
+        kind: ArgKind::ChoiceOrNumber(SENSOR_NAMES),
+        optional: false,
+        help: "the light sensor by its id or name or the temperature sensor",
+    },
+    ArgSpec {
+        name: "ms",
+        kind: ArgKind::Number,
+        optional: false,
+        help: "the period 10..3600000 of the continuous measurement, at least the conversion time",
+    },
+];
+
+const MODE_ARGS: &[ArgSpec] = &[ArgSpec {
+    name: "encoding",
+    kind: ArgKind::Choice(CONSOLE_MODES),
+    optional: false,
+    help: "binary switches to COBS framed messages, a framed mode text switches back",
+}];
+
+const I2C_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "op",
+        kind: ArgKind::Choice(I2C_OPS),
+        optional: false,
+        help: "scan probes the addresses 0x08..0x77, wr writes and reads with a repeated start",
+    },
+    ArgSpec {
+        name: "addr",
+        kind: ArgKind::Number,
+        optional: true,
+        help: "the address of the device in hex, e.g. 0x23",
+    },
+    ArgSpec {
+        name: "bytes",
+        kind: ArgKind::Number,
+        optional: true,
+        help: "the hex bytes to write, e.g. 0110, or the number of bytes to read 1..16",
+    },
+    ArgSpec {
+        name: "len",
+        kind: ArgKind::Number,
+        optional: true,
+        help: "the number of bytes wr reads 1..16",
+    },
+];
+
+const ONEWIRE_ARGS: &[ArgSpec] = &[ArgSpec {
+    name: "op",
+    kind: ArgKind::Choice(ONEWIRE_OPS),
+    optional: false,
+    help: "scan lists the family code and serial of every device, alarms only the ones beyond their alarm limits",
+}];
+
+const HELP_ARGS: &[ArgSpec] = &[ArgSpec {
+    name: "command",
+    kind: ArgKind::Command,
+    optional: true,
+    help: "shows the arguments of the given command",
+}];
+
+/// All commands understood by the console
+pub static COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "led",
+        aliases: &[],
+        args: LED_ARGS,
+        help: "controls a user LED",
+        parse: parse_led,
+    },
+    CommandSpec {
+        name: "status",
+        aliases: &[],
+        args: STATUS_ARGS,
+        help: "sets the interval of the status report",
+        parse: parse_status,
+    },
+    CommandSpec {
+        name: "light",
+        aliases: &[],
+        args: LIGHT_ARGS,
+        help: "controls one of the BH1750FVI light sensors",
+        parse: parse_light,
+    },
+    CommandSpec {
+        name: "temp",
+        aliases: &[],
+        args: TEMP_ARGS,
+        help: "controls the DS18B20 temperature sensor on the 1-Wire bus",
+        parse: parse_temp,
+    },
+    CommandSpec {
+        name: "get",
+        aliases: &[],
+        args: GET_ARGS,
+        help: "reads back a state in the syntax of the command that sets it",
+        parse: parse_get,
+    },
+    CommandSpec {
+        name: "state",
+        aliases: &[],
+        args: &[],
+        help: "reads back the whole state",
+        parse: parse_state,
+    },
+    CommandSpec {
+        name: "mode",
+        aliases: &[],
+        args: MODE_ARGS,
+        help: "switches the encoding of the console",
+        parse: parse_mode,
+    },
+    CommandSpec {
+        name: "i2c",
+        aliases: &[],
+        args: I2C_ARGS,
+        help: "accesses the shared I2C bus directly",
+        parse: parse_i2c,
+    },
+    CommandSpec {
+        name: "onewire",
+        aliases: &[],
+        args: ONEWIRE_ARGS,
+        help: "accesses the 1-Wire bus directly",
+        parse: parse_onewire,
+    },
+    CommandSpec {
+        name: "sample",
+        aliases: &[],
+        args: SAMPLE_ARGS,
+        help: "sets the sampling period of a sensor",
+        parse: parse_sample,
+    },
+    CommandSpec {
+        name: "help",
+        aliases: &["?"],
+        args: HELP_ARGS,
+        help: "lists the commands or the usage of one command",
+        parse: parse_help,
+    },
+];
+
+/// Finds a command by its name or one of its aliases, gives its index in [COMMANDS] too
+pub fn find_command(name: &str) -> Option<(u8, &'static CommandSpec)> {
+    COMMANDS
+        .iter()
+        .enumerate()
+        .find(|(_, spec)| spec.name == name || spec.aliases.contains(&name))
+        .map(|(idx, spec)| (idx as u8, spec))
+}
+
+/// Parses the message by dispatching the first word to the matching [CommandSpec]
+pub fn dispatch(msg: &str) -> Result<Commands, CommandParseError<'_>> {
+    let mut split = msg.split_whitespace();
+    let verb = split.next().unwrap_or("");
+    let (_, spec) = find_command(verb).ok_or(CommandParseError::UnknownCommand(verb))?;
+
+    let mut args = Arguments { verb, spec, split, pos: 0 };
+    (spec.parse)(&mut args)
+}
+
+fn parse_led_color(color: &str) -> Result<u8, CommandParseError<'_>> {
+    match color {
+        "r" | "red" => Ok(1),
+        "g" | "green" => Ok(2),
+        "b" | "blue" => Ok(3),
+        _ => Err(CommandParseError::UnknownLedColor(color, LED_COLORS)),
+    }
+}
+
+fn parse_light_sensor(sensor: &str) -> Result<u8, CommandParseError<'_>> {
+    let by_name = LIGHT_SENSOR_NAMES.iter().position(|name| *name == sensor).map(|idx| idx + 1);
+    let by_id = sensor.parse::<usize>().ok().filter(|id| (1..=LIGHT_SENSOR_NAMES.len()).contains(id));
+    by_name
+        .or(by_id)
+        .map(|id| id as u8)
+        .ok_or(CommandParseError::UnknownLightSensor(sensor, &LIGHT_SENSOR_NAMES))
+}
+
+fn parse_sensor(sensor: &str) -> Result<SensorId, CommandParseError<'_>> {
+    match sensor {
+        "temp" => Ok(SensorId::Temperature),
+        _ => parse_light_sensor(sensor)
+            .map(SensorId::Light)
+            .map_err(|_| CommandParseError::UnknownSensor(sensor, SENSOR_NAMES)),
+    }
+}
+
+fn parse_led<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let color = args.required()?;
+    let func = args.required()?;
+
+    let id = parse_led_color(color)?;
+    let inner = str_to_led_state(func).ok_or(CommandParseError::BadNumber(func))?;
+
+    Ok(Commands::Led(id, inner))
+}
+
+fn parse_status<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    Ok(Commands::UartStatusReport(args.number()?))
+}
+
+fn parse_light<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let id = parse_light_sensor(args.required()?)?;
+    let sub_cmd = args.required()?;
+    let sub_cmd = match sub_cmd {
+        "s" | "single" => LightSensorCommands::SingleMeasurment,
+        "c" | "continious" => LightSensorCommands::ContiniousMeasurement,
+        "off" => LightSensorCommands::Off,
+        "mode" => {
+            let token = args.required()?;
+            let resolution = match token {
+                "hr" => LightResolution::High,
+                "hr2" => LightResolution::High2,
+                "lr" => LightResolution::Low,
+                _ => return Err(CommandParseError::UnknownLightResolution(token, LIGHT_RESOLUTIONS)),
+            };
+            LightSensorCommands::Resolution(resolution)
+        }
+        "mtreg" => LightSensorCommands::MeasurementTime(args.number_in(MTREG_RANGE)? as u8),
+        _ => return Err(CommandParseError::UnknownLightCommand(sub_cmd, LIGHT_STATES)),
+    };
+    Ok(Commands::LightSensor(id, sub_cmd))
+}
+
+fn parse_temp<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let sub_cmd = args.required()?;
+    let sub_cmd = match sub_cmd {
+        "s" | "single" => TemperatureSensorCommands::SingleMeasurement,
+        "c" | "continuous" => TemperatureSensorCommands::ContinuousMeasurement,
+        "off" => TemperatureSensorCommands::Off,
+        "res" => TemperatureSensorCommands::Resolution(args.number_in(TEMP_RESOLUTION_RANGE)? as u8),
+        "alarm" => {
+            let lo = args.signed_in(TEMP_RANGE)?;
+            let hi = args.signed_in(lo..=*TEMP_RANGE.end())?;
+            TemperatureSensorCommands::Alarm(lo as i8, hi as i8)
+        }
+        _ => return Err(CommandParseError::UnknownTemperatureCommand(sub_cmd, TEMP_STATES)),
+    };
+    Ok(Commands::TemperatureSensor(sub_cmd))
+}
+
+fn parse_get<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let what = args.required()?;
+    let query = match what {
+        "led" => Query::Led(parse_led_color(args.required()?)?),
+        "status" => Query::Status,
+        "light" => Query::Light(parse_light_sensor(args.required()?)?),
+        "temp" => Query::Temperature,
+        "sample" => Query::Sample(parse_sensor(args.required()?)?),
+        _ => return Err(CommandParseError::UnknownQuery(what, QUERIES)),
+    };
+    Ok(Commands::Get(query))
+}
+
+fn parse_state<'a>(_args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    Ok(Commands::State)
+}
+
+fn parse_mode<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let encoding = args.required()?;
+    match encoding {
+        "text" => Ok(Commands::Mode(ConsoleMode::Text)),
+        "binary" => Ok(Commands::Mode(ConsoleMode::Binary)),
+        _ => Err(CommandParseError::UnknownConsoleMode(encoding, CONSOLE_MODES)),
+    }
+}
+
+fn parse_i2c_addr(token: &str) -> Result<u8, CommandParseError<'_>> {
+    let digits = token.strip_prefix("0x").unwrap_or(token);
+    u8::from_str_radix(digits, 16)
+        .ok()
+        .filter(|addr| SCAN_RANGE.contains(addr) && !digits.starts_with('+'))
+        .ok_or(CommandParseError::BadAddress(token))
+}
+
+fn parse_i2c_bytes(token: &str) -> Result<I2cBytes, CommandParseError<'_>> {
+    I2cBytes::from_hex(token).ok_or(CommandParseError::BadHex(token))
+}
+
+fn parse_i2c<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let op = args.required()?;
+    let len_range = 1..=I2C_MAX_LEN as u32;
+    let op = match op {
+        "scan" => I2cCommands::Scan,
+        "write" => {
+            let addr = parse_i2c_addr(args.required()?)?;
+            I2cCommands::Write(addr, parse_i2c_bytes(args.required()?)?)
+        }
+        "read" => {
+            let addr = parse_i2c_addr(args.required()?)?;
+            I2cCommands::Read(addr, args.number_in(len_range)? as u8)
+        }
+        "wr" => {
+            let addr = parse_i2c_addr(args.required()?)?;
+            let bytes = parse_i2c_bytes(args.required()?)?;
+            I2cCommands::WriteRead(addr, bytes, args.number_in(len_range)? as u8)
+        }
+        _ => return Err(CommandParseError::UnknownI2cCommand(op, I2C_OPS)),
+    };
+    Ok(Commands::I2cBus(op))
+}
+
+fn parse_onewire<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let op = args.required()?;
+    match op {
+        "scan" => Ok(Commands::OneWireBus(OneWireCommands::Scan)),
+        "alarms" => Ok(Commands::OneWireBus(OneWireCommands::AlarmSearch)),
+        _ => Err(CommandParseError::UnknownOneWireCommand(op, ONEWIRE_OPS)),
+    }
+}
+
+fn parse_sample<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    let sensor = parse_sensor(args.required()?)?;
+    Ok(Commands::Sample(sensor, args.number_in(SAMPLE_PERIOD_RANGE)?))
+}
+
+fn parse_help<'a>(args: &mut Arguments<'a>) -> Result<Commands, CommandParseError<'a>> {
+    match args.optional() {
+        Some(name) => {
+            let (idx, _) = find_command(name).ok_or(CommandParseError::UnknownCommand(name))?;
+            Ok(Commands::Help(Some(idx)))
+        }
+        None => Ok(Commands::Help(None)),
+    }
+}
+
+impl fmt::Display for ArgSpec {
+    /// Renders the argument in the compact usage form, e.g. `r|g|b` or `on|off|<ms>`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.optional {
+            f.write_str("[")?;
+        }
+        match self.kind {
+            ArgKind::Choice(choices) => write_choices(f, choices)?,
+            ArgKind::Number => write!(f, "<{}>", self.name)?,
+            ArgKind::ChoiceOrNumber(choices) => {
+                write_choices(f, choices)?;
+                write!(f, "|<{}>", self.name)?;
+            }
+            ArgKind::Command => write!(f, "<{}>", self.name)?,
+        }
+        if self.optional {
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CommandSpec {
+    /// Renders the one line usage, e.g. `led r|g|b on|off|<ms> - controls a user LED`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        for alias in self.aliases {
+            write!(f, "|{}", alias)?;
+        }
+        for arg in self.args {
+            write!(f, " {}", arg)?;
+        }
+        write!(f, " - {}", self.help)
+    }
+}
+
+fn write_choices(f: &mut fmt::Formatter<'_>, choices: &[&str]) -> fmt::Result {
+    for (idx, choice) in choices.iter().enumerate() {
+        if idx > 0 {
+            f.write_str("|")?;
+        }
+        f.write_str(choice)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+    use heapless::String;
+
+    fn render(value: &dyn fmt::Display) -> String<128> {
+        let mut buf = String::new();
+        write!(buf, "{}", value).unwrap();
+        buf
+    }
+
+    #[test]
+    fn errors_list_the_choices_of_the_schema() {
+        let cases = [
+            ("led x on", "led", 0, "unknown led color 'x', use "),
+            ("light 1 on", "light", 1, "unknown light command 'on', use "),
+            ("light 1 mode hr3", "light", 2, "unknown light resolution 'hr3', use "),
+            ("temp on", "temp", 0, "unknown temp command 'on', use "),
+            ("get humidity", "get", 0, "unknown query 'humidity', use "),
+            ("mode hex", "mode", 0, "unknown console mode 'hex', use "),
+            ("i2c probe", "i2c", 0, "unknown i2c command 'probe', use "),
+            ("onewire search", "onewire", 0, "unknown onewire command 'search', use "),
+            ("light 3 s", "light", 0, "unknown light sensor '3', use 1..2|"),
+            ("sample 3 500", "sample", 0, "unknown sensor '3', use 1..2|"),
+        ];
+        for (msg, verb, arg, prefix) in cases {
+            let (_, spec) = find_command(verb).unwrap();
+            let (ArgKind::Choice(choices) | ArgKind::ChoiceOrNumber(choices)) = spec.args[arg].kind else {
+                panic!("argument {} of {} has no choices", arg, verb);
+            };
+            let mut expected: String<128> = String::new();
+            write!(expected, "{}{}", prefix, render(&Choices(choices))).unwrap();
+            assert_eq!(render(&dispatch(msg).unwrap_err()), expected, "{}", msg);
+        }
+        let error = dispatch("temp on").unwrap_err();
+        assert_eq!(render(&error), "unknown temp command 'on', use s|c|off|res|alarm");
+    }
+
+    #[test]
+    fn names_and_aliases_are_unique() {
+        for (idx, spec) in COMMANDS.iter().enumerate() {
+            assert_eq!(find_command(spec.name).map(|(i, _)| i as usize), Some(idx));
+            for alias in spec.aliases {
+                assert_eq!(find_command(alias).map(|(i, _)| i as usize), Some(idx));
+            }
+        }
+    }
+
+    #[test]
+    fn usage_is_generated_from_schema() {
+        let (_, led) = find_command("led").unwrap();
+        assert_eq!(render(led), "led r|g|b on|off|<ms> - controls a user LED");
+        let (_, status) = find_command("status").unwrap();
+        assert_eq!(render(status), "status <ms> - sets the interval of the status report");
+        let (_, light) = find_command("light").unwrap();
+        assert_eq!(
+            render(light),
+            "light indoor|window|<sensor> s|c|off|mode|mtreg [hr|hr2|lr|<value>] - controls one of the BH1750FVI light sensors"
+        );
+        let (_, temp) = find_command("temp").unwrap();
+        assert_eq!(
+            render(temp),
+            "temp s|c|off|res|alarm [<value>] [<hi>] - controls the DS18B20 temperature sensor on the 1-Wire bus"
+        );
+        let (_, i2c) = find_command("i2c").unwrap();
+        assert_eq!(
+            render(i2c),
+            "i2c scan|write|read|wr [<addr>] [<bytes>] [<len>] - accesses the shared I2C bus directly"
+        );
+        let (_, onewire) = find_command("onewire").unwrap();
+        assert_eq!(render(onewire), "onewire scan|alarms - accesses the 1-Wire bus directly");
+        let (_, sample) = find_command("sample").unwrap();
+        assert_eq!(render(sample), "sample indoor|window|temp|<sensor> <ms> - sets the sampling period of a sensor");
+        let (_, help) = find_command("?").unwrap();
+        assert_eq!(render(help), "help|? [<command>] - lists the commands or the usage of one command");
+    }
+
+    #[test]
+    fn help() {
+        let (idx, _) = find_command("light").unwrap();
+        assert_eq!(dispatch("help"), Ok(Commands::Help(None)));
+        assert_eq!(dispatch("?"), Ok(Commands::Help(None)));
+        assert_eq!(dispatch("help light"), Ok(Commands::Help(Some(idx))));
+        assert_eq!(dispatch("help foo"), Err(CommandParseError::UnknownCommand("foo")));
+    }
+
+    #[test]
+    fn missing_arguments_are_named_by_schema() {
+        assert_eq!(dispatch("led r"), Err(CommandParseError::MissingArgument("led", "ms")));
+        assert_eq!(dispatch("light"), Err(CommandParseError::MissingArgument("light", "sensor")));
+        assert_eq!(dispatch("light 1"), Err(CommandParseError::MissingArgument("light", "state")));
+    }
+}
@@ -0,0 +1,357 @@
+//! The periodic status report that is sent over the text console, see [StatusReport].
+//!
+//! The report is a single line that is written by the firmware and parsed by the host tool, it
+//! gives the state and last value of every light sensor in the order of their ids and of the
+//! temperature sensor, followed by the number of I2C bus recoveries since the start of the firmware:
+//!
+//! ```text
+//! Status: Light Sensor 1 on - 1234.5 Lux, Light Sensor 2 off - No sensor value yet, Temperature on - 21.06 °C, I2C recoveries 0
+//! Status: Light Sensor 1 on - sensor error: NACK @0x23, Light Sensor 2 single - 12.0 Lux, Temperature on - sensor error: CRC mismatch, I2C recoveries 2
+//! ```
+
+use core::fmt;
+use core::str::FromStr;
+
+use nucleo_types::error::{BusFault, OneWireFault};
+use nucleo_types::units::{Celsius, Lux};
+
+use crate::cmd::{LightSensorState, TemperatureSensorState, LIGHT_SENSOR_COUNT};
+
+/// The prefix of every status report line
+pub const STATUS_PREFIX: &str = "Status: ";
+
+/// The state and last result of one light sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightReport {
+    /// the state the light sensor is operated in
+    pub state: LightSensorState,
+
+    /// the result of the last measurement of the light sensor if there is one
+    pub value: Option<Result<Lux, BusFault>>,
+}
+
+/// The state and last result of the temperature sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureReport {
+    /// the state the temperature sensor is operated in
+    pub state: TemperatureSensorState,
+
+    /// the result of the last measurement of the temperature sensor if there is one
+    pub value: Option<Result<Celsius, OneWireFault>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusReport {
+    /// the light sensors, the report of the sensor with id 1 comes first
+    pub light: [LightReport; LIGHT_SENSOR_COUNT],
+
+    pub temperature: TemperatureReport,
+
+    /// the number of recoveries of the I2C bus since the start
+    pub bus_recoveries: u32,
+}
+
+/// The line is not a status report or it is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportParseError;
+
+impl fmt::Display for ReportParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not a valid status report")
+    }
+}
+
+/// The last result of a sensor if there is one, a value or the fault that prevented it
+type SensorValue<T, F> = Option<Result<T, F>>;
+
+/// Writes the last result of a sensor, the value is followed by the given unit
+fn write_value<T: fmt::Display, F: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    value: &SensorValue<T, F>,
+    unit: &str,
+) -> fmt::Result {
+    match value {
+        Some(Ok(value)) => write!(f, " - {} {}", value, unit),
+        Some(Err(fault)) => write!(f, " - sensor error: {}", fault),
+        None => f.write_str(" - No sensor value yet"),
+    }
+}
+
+/// The inverse of [write_value], gives the state token and the last result
+fn parse_value<'a, T: FromStr, F: FromStr>(
+    text: &'a str,
+    unit: &str,
+) -> Result<(&'a str, SensorValue<T, F>), ReportParseError> {
+    let (state, value) = text.split_once(" - ").ok_or(ReportParseError)?;
+    let value = match value {
+        "No sensor value yet" => None,
+        value => match value.strip_prefix("sensor error: ") {
+            Some(fault) => Some(Err(fault.parse().map_err(|_| ReportParseError)?)),
+            None => {
+                let value = value
+                    .strip_suffix(unit)
+                    .and_then(|value| value.strip_suffix(' '))
+                    .ok_or(ReportParseError)?;
+                Some(Ok(value.parse().map_err(|_| ReportParseError)?))
+            }
+        },
+    };
+    Ok((state, value))
+}
+
+impl fmt::Display for LightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.state.as_str())?;
+        write_value(f, &self.value, "Lux")
+    }
+}
+
+impl FromStr for LightReport {
+    type Err = ReportParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (state, value) = parse_value(text, "Lux")?;
+        let state = LightSensorState::parse(state).ok_or(ReportParseError)?;
+        Ok(LightReport { state, value })
+    }
+}
+
+impl fmt::Display for TemperatureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.state.as_str())?;
+        write_value(f, &self.value, "°C")
+    }
+}
+
+impl FromStr for TemperatureReport {
+    type Err = ReportParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (state, value) = parse_value(text, "°C")?;
+        let state = TemperatureSensorState::parse(state).ok_or(ReportParseError)?;
+        Ok(TemperatureReport { state, value })
+    }
+}
+
+impl fmt::Display for StatusReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(STATUS_PREFIX)?;
+        for (idx, light) in self.light.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "Light Sensor {} {}", idx + 1, light)?;
+        }
+        write!(f, ", Temperature {}", self.temperature)?;
+        write!(f, ", I2C recoveries {}", self.bus_recoveries)
+    }
+}
+
+impl FromStr for StatusReport {
+    type Err = ReportParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line
+            .trim_end()
+            .strip_prefix(STATUS_PREFIX)
+            .ok_or(ReportParseError)?
+            .split(", ");
+
+        let mut light = [LightReport {
+            state: LightSensorState::PowerOff,
+            value: None,
+        }; LIGHT_SENSOR_COUNT];
+        for (idx, report) in light.iter_mut().enumerate() {
+            let part = parts.next().ok_or(ReportParseError)?;
+            let (id, rest) = part
+                .strip_prefix("Light Sensor ")
+                .and_then(|rest| rest.split_once(' '))
+                .ok_or(ReportParseError)?;
+            if id.parse::<usize>() != Ok(idx + 1) {
+                return Err(ReportParseError);
+            }
+            *report = rest.parse()?;
+        }
+        let temperature = parts
+            .next()
+            .and_then(|part| part.strip_prefix("Temperature "))
+            .ok_or(ReportParseError)?
+            .parse()?;
+        let bus_recoveries = parts
+            .next()
+            .and_then(|part| part.strip_prefix("I2C recoveries "))
+            .and_then(|count| count.parse().ok())
+            .ok_or(ReportParseError)?;
+        if parts.next().is_some() {
+            return Err(ReportParseError);
+        }
+
+        Ok(StatusReport {
+            light,
+            temperature,
+            bus_recoveries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+    use heapless::String;
+    use nucleo_types::error::BusFaultKind;
+
+    fn to_line(report: &StatusReport) -> String<256> {
+        let mut line = String::new();
+        write!(line, "{}", report).unwrap();
+        line
+    }
+
+    const NO_VALUE: LightReport = LightReport {
+        state: LightSensorState::PowerOff,
+        value: None,
+    };
+
+    const NO_TEMPERATURE: TemperatureReport = TemperatureReport {
+        state: TemperatureSensorState::PowerOff,
+        value: None,
+    };
+
+    #[test]
+    fn display() {
+        let report = StatusReport {
+            light: [
+                LightReport {
+                    state: LightSensorState::ContiniousMeasurement,
+                    value: Some(Ok(Lux::from_millilux(1_234_567))),
+                },
+                NO_VALUE,
+            ],
+            temperature: TemperatureReport {
+                state: TemperatureSensorState::ContinuousMeasurement,
+                value: Some(Ok(Celsius::from_millidegrees(21_062))),
+            },
+            bus_recoveries: 0,
+        };
+        assert_eq!(
+            to_line(&report),
+            "Status: Light Sensor 1 on - 1234.5 Lux, Light Sensor 2 off - No sensor value yet, \
+             Temperature on - 21.06 °C, I2C recoveries 0"
+        );
+
+        let report = StatusReport {
+            light: [
+                NO_VALUE,
+                LightReport {
+                    state: LightSensorState::ContiniousMeasurement,
+                    value: Some(Err(BusFault {
+                        kind: BusFaultKind::Nack,
+                        addr: 0x5C,
+                    })),
+                },
+            ],
+            temperature: TemperatureReport {
+                state: TemperatureSensorState::SingleMeasurement,
+                value: Some(Err(OneWireFault::Crc)),
+            },
+            bus_recoveries: 3,
+        };
+        assert_eq!(
+            to_line(&report),
+            "Status: Light Sensor 1 off - No sensor value yet, Light Sensor 2 on - sensor error: NACK @0x5C, \
+             Temperature single - sensor error: CRC mismatch, I2C recoveries 3"
+        );
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        let states = [
+            LightSensorState::PowerOff,
+            LightSensorState::SingleMeasurement,
+            LightSensorState::ContiniousMeasurement,
+        ];
+        let values = [
+            None,
+            Some(Ok(Lux::from_lux(0))),
+            Some(Ok(Lux::from_millilux(121_556_800))),
+            Some(Err(BusFault {
+                kind: BusFaultKind::Timeout,
+                addr: 0x23,
+            })),
+        ];
+        for state in states {
+            for value in values {
+                let light = LightReport { state, value };
+                for report in [
+                    StatusReport { light: [light, NO_VALUE], temperature: NO_TEMPERATURE, bus_recoveries: 0 },
+                    StatusReport { light: [NO_VALUE, light], temperature: NO_TEMPERATURE, bus_recoveries: u32::MAX },
+                ] {
+                    assert_eq!(to_line(&report).parse(), Ok(report));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn temperature_roundtrip() {
+        let states = [
+            TemperatureSensorState::PowerOff,
+            TemperatureSensorState::SingleMeasurement,
+            TemperatureSensorState::ContinuousMeasurement,
+        ];
+        let values = [
+            None,
+            Some(Ok(Celsius::from_millidegrees(125_000))),
+            Some(Ok(Celsius::from_millidegrees(-10_120))),
+            Some(Ok(Celsius::from_millidegrees(-500))),
+            Some(Err(OneWireFault::NoPresence)),
+            Some(Err(OneWireFault::Pin)),
+        ];
+        for state in states {
+            for value in values {
+                let report = StatusReport {
+                    light: [NO_VALUE; LIGHT_SENSOR_COUNT],
+                    temperature: TemperatureReport { state, value },
+                    bus_recoveries: 1,
+                };
+                assert_eq!(to_line(&report).parse(), Ok(report));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        const LIGHTS: &str = "Status: Light Sensor 1 on - 12 Lux, Light Sensor 2 off - No sensor value yet";
+        for line in [
+            "OK light 1 single",
+            "Status: Light Sensor 1 on - 12 Lux",
+            "Status: Light Sensor 1 dim - 12 Lux, Light Sensor 2 off - No sensor value yet",
+            "Status: Light Sensor 1 on - 12, Light Sensor 2 off - No sensor value yet",
+            "Status: Light Sensor 1 on - 1.2.3 Lux, Light Sensor 2 off - No sensor value yet",
+            "Status: Light Sensor 1 on - many Lux, Light Sensor 2 off - No sensor value yet",
+            "Status: Light Sensor 1 on - sensor error: 12 Lux, Light Sensor 2 off - No sensor value yet",
+            "Status: Light Sensor 2 on - 12 Lux, Light Sensor 1 off - No sensor value yet",
+            "Status: Light Sensor 1 on - 12 Lux, Light Sensor 2 on - 12 Lux, Light Sensor 3 on - 12 Lux",
+            LIGHTS,
+            "Status: Light Sensor 1 on - 12 Lux, Light Sensor 2 off - No sensor value yet, I2C recoveries 1",
+        ] {
+            assert_eq!(line.parse::<StatusReport>(), Err(ReportParseError), "{}", line);
+        }
+        for rest in [
+            ", Temperature on - 21.06 °C",
+            ", Temperature on - 21.06 C, I2C recoveries 1",
+            ", Temperature on - 21.06 Lux, I2C recoveries 1",
+            ", Temperature hot - 21.06 °C, I2C recoveries 1",
+            ", Temperature on - sensor error: NACK @0x23, I2C recoveries 1",
+            ", Temperature on - 21.06 °C, I2C recoveries -1",
+            ", Temperature on - 21.06 °C, I2C recoveries 1, x",
+        ] {
+            let mut line = String::<256>::new();
+            write!(line, "{}{}", LIGHTS, rest).unwrap();
+            assert_eq!(line.parse::<StatusReport>(), Err(ReportParseError), "{}", line);
+        }
+        let line = "Status: Light Sensor 1 on - 12 Lux, Light Sensor 2 off - No sensor value yet, \
+                    Temperature off - No sensor value yet, I2C recoveries 1\r";
+        assert!(line.parse::<StatusReport>().is_ok());
+    }
+}
@@ -0,0 +1,287 @@
+//! Responses of the command executor that are sent back to the operator over the console.
+//!
+//! Every command is answered by zero or more value lines followed by exactly one final line
+//! that is either `OK <cmd>` or `ERR <reason>`. This allows scripts driving the board over serial
+//! to collect the values of a command until the final line tells whether it worked:
+//!
+//! ```text
+//! > light 1 single
+//! VAL light 1 1234.5
+//! OK light 1 single
+//! > status abc
+//! ERR 'abc' is not a valid number
+//! ```
+//!
+//! The `help` command answers with [Response::Usage] and [Response::ArgUsage] lines that are
+//! generated from the [command registry](crate::registry).
+//!
+//! `i2c scan` answers with a grid like the one of `i2cdetect`, followed by a value line per
+//! device, that names the part if a driver knows its address:
+//!
+//! ```text
+//!      0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f
+//! 00:                         -- -- -- -- -- -- -- --
+//! 10: -- -- -- -- -- -- -- -- -- -- -- -- -- -- -- --
+//! 20: -- -- -- 23 -- -- -- -- -- -- -- -- -- -- -- --
+//! ...
+//! VAL i2c 0x23 BH1750
+//! ```
+//!
+//! `temp single` answers with the temperature in °C:
+//!
+//! ```text
+//! VAL temp 21.06
+//! ```
+//!
+//! `onewire scan` answers with a value line per device, it gives the family code and serial of
+//! the ROM, names the part if its family is known and tells how the device is powered:
+//!
+//! ```text
+//! VAL onewire 28-00000f1e64ff DS18B20 parasitic
+//! ```
+
+use core::fmt::{self, Write};
+
+use heapless::String;
+use serde::{Deserialize, Serialize};
+
+use nucleo_types::onewire::{known_family, PowerMode, Rom};
+use nucleo_types::scan::{known_device, SCAN_RANGE};
+use nucleo_types::units::{Celsius, Lux};
+
+use crate::cmd::{Commands, I2cBytes};
+use crate::registry::COMMANDS;
+
+/// The human readable reason of an [Response::Err], longer reasons are truncated
+pub type ErrorReason = String<64>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Response {
+    /// the command has been executed successfully, final line
+    Ok(Commands),
+
+    /// the command could not be parsed or executed, final line
+    Err(ErrorReason),
+
+    /// a reading of the light sensor with the given id as result of a measurement
+    LightValue(u8, Lux),
+
+    /// a reading of the temperature sensor as result of a measurement
+    TemperatureValue(Celsius),
+
+    /// a part of the state expressed as the command that sets it
+    State(Commands),
+
+    /// the usage line of the command with the given index in [COMMANDS]
+    Usage(u8),
+
+    /// the description of an argument given by command and argument index
+    ArgUsage(u8, u8),
+
+    /// the column header of the grid of an I2C scan
+    ScanHeader,
+
+    /// a row of the grid of an I2C scan, the bits of the acknowledged addresses `row * 0x10 + bit`
+    ScanRow(u8, u16),
+
+    /// a device found by an I2C scan at the given address
+    Device(u8),
+
+    /// the bytes read from the device at the given address by a raw I2C transfer
+    I2cData(u8, I2cBytes),
+
+    /// a device found by a 1-Wire scan and how it is powered
+    OneWireDevice(Rom, PowerMode),
+}
+
+impl Response {
+    /// Generates an [Response::Err] from anything that displays, e.g. a [CommandParseError](crate::CommandParseError)
+    pub fn error(reason: &dyn fmt::Display) -> Self {
+        let mut msg = ErrorReason::new();
+        // a truncated reason is still better than none
+        let _ = write!(msg, "{}", reason);
+        Response::Err(msg)
+    }
+
+    /// Indicates if this response is the last line of the answer to a command
+    pub fn is_final(&self) -> bool {
+        matches!(self, Response::Ok(_) | Response::Err(_))
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Response::Ok(cmd) => write!(f, "OK {}", cmd),
+            Response::Err(reason) => write!(f, "ERR {}", reason),
+            Response::LightValue(id, value) => write!(f, "VAL light {} {}", id, value),
+            Response::TemperatureValue(value) => write!(f, "VAL temp {}", value),
+            Response::State(cmd) => write!(f, "VAL {}", cmd),
+            Response::Usage(cmd_idx) => match COMMANDS.get(*cmd_idx as usize) {
+                Some(spec) => write!(f, "{}", spec),
+                None => f.write_str("?"),
+            },
+            Response::ArgUsage(cmd_idx, arg_idx) => {
+                let arg = COMMANDS
+                    .get(*cmd_idx as usize)
+                    .and_then(|spec| spec.args.get(*arg_idx as usize));
+                match arg {
+                    Some(arg) => write!(f, "  {} = {} - {}", arg.name, arg, arg.help),
+                    None => f.write_str("?"),
+                }
+            }
+            Response::ScanHeader => {
+                f.write_str("   ")?;
+                for col in 0..16 {
+                    write!(f, "  {:x}", col)?;
+                }
+                Ok(())
+            }
+            Response::ScanRow(row, acked) => {
+                write!(f, "{:02x}:", row << 4)?;
+                for col in 0..16 {
+                    let addr = (row << 4) | col;
+                    if !SCAN_RANGE.contains(&addr) {
+                        f.write_str("   ")?;
+                    } else if acked & (1 << col) != 0 {
+                        write!(f, " {:02x}", addr)?;
+                    } else {
+                        f.write_str(" --")?;
+                    }
+                }
+                Ok(())
+            }
+            Response::Device(addr) => match known_device(*addr) {
+                Some(name) => write!(f, "VAL i2c 0x{:02X} {}", addr, name),
+                None => write!(f, "VAL i2c 0x{:02X}", addr),
+            },
+            Response::I2cData(addr, bytes) => {
+                write!(f, "VAL i2c 0x{:02X}", addr)?;
+                for byte in bytes.as_slice() {
+                    write!(f, " {:02x}", byte)?;
+                }
+                Ok(())
+            }
+            Response::OneWireDevice(rom, power) => match known_family(rom.family_code()) {
+                Some(name) => write!(f, "VAL onewire {} {} {}", rom, name, power),
+                None => write!(f, "VAL onewire {} {}", rom, power),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::{str_to_command, LightSensorCommands};
+    use crate::led::LedState;
+
+    fn render(response: &Response) -> String<96> {
+        let mut buf = String::new();
+        write!(buf, "{}", response).unwrap();
+        buf
+    }
+
+    #[test]
+    fn acknowledge() {
+        let response = Response::Ok(Commands::Led(2, LedState::Toggle(300)));
+        assert_eq!(render(&response), "OK led g 300");
+        assert!(response.is_final());
+    }
+
+    #[test]
+    fn parse_error() {
+        let err = str_to_command("status abc").unwrap_err();
+        let response = Response::error(&err);
+        assert_eq!(render(&response), "ERR 'abc' is not a valid number");
+        assert!(response.is_final());
+    }
+
+    #[test]
+    fn value() {
+        let response = Response::LightValue(2, Lux::from_millilux(1_234_567));
+        assert_eq!(render(&response), "VAL light 2 1234.5");
+        assert!(!response.is_final());
+        assert_eq!(
+            render(&Response::Ok(Commands::LightSensor(2, LightSensorCommands::SingleMeasurment))),
+            "OK light 2 single"
+        );
+    }
+
+    #[test]
+    fn temperature_value() {
+        let response = Response::TemperatureValue(Celsius::from_millidegrees(-10_125));
+        assert_eq!(render(&response), "VAL temp -10.12");
+        assert!(!response.is_final());
+    }
+
+    #[test]
+    fn state_uses_command_syntax() {
+        let response = Response::State(Commands::UartStatusReport(10000));
+        assert_eq!(render(&response), "VAL status 10000");
+        assert!(!response.is_final());
+        assert_eq!(render(&Response::State(Commands::Led(3, LedState::Manual(false)))), "VAL led b off");
+    }
+
+    #[test]
+    fn usage() {
+        let (idx, _) = crate::registry::find_command("led").unwrap();
+        assert_eq!(render(&Response::Usage(idx)), "led r|g|b on|off|<ms> - controls a user LED");
+        assert_eq!(
+            render(&Response::ArgUsage(idx, 0)),
+            "  color = r|g|b - red, green or blue user LED, the long names may be used too"
+        );
+        assert!(!Response::ArgUsage(idx, 0).is_final());
+    }
+
+    #[test]
+    fn scan_grid() {
+        assert_eq!(
+            render(&Response::ScanHeader),
+            "     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f"
+        );
+        assert_eq!(
+            render(&Response::ScanRow(0, 1 << 8)),
+            "00:                         08 -- -- -- -- -- -- --"
+        );
+        assert_eq!(
+            render(&Response::ScanRow(2, 1 << 3)),
+            "20: -- -- -- 23 -- -- -- -- -- -- -- -- -- -- -- --"
+        );
+        assert_eq!(
+            render(&Response::ScanRow(7, 0xFFFF)),
+            "70: 70 71 72 73 74 75 76 77                        "
+        );
+        assert_eq!(render(&Response::Device(0x5C)), "VAL i2c 0x5C BH1750");
+        assert_eq!(render(&Response::Device(0x40)), "VAL i2c 0x40");
+        assert!(!Response::ScanRow(0, 0).is_final());
+    }
+
+    #[test]
+    fn i2c_data() {
+        let bytes = I2cBytes::new(&[0x01, 0xab, 0x00]).unwrap();
+        assert_eq!(render(&Response::I2cData(0x23, bytes)), "VAL i2c 0x23 01 ab 00");
+        assert!(!Response::I2cData(0x23, bytes).is_final());
+    }
+
+    #[test]
+    fn onewire_devices() {
+        let ds18b20 = Rom([0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x34]);
+        let parasitic = Response::OneWireDevice(ds18b20, PowerMode::Parasitic);
+        assert_eq!(render(&parasitic), "VAL onewire 28-00000f1e64ff DS18B20 parasitic");
+        let unknown = Rom([0x01, 0x3D, 0x12, 0x6B, 0x0B, 0x08, 0x00, 0x00]);
+        let external = Response::OneWireDevice(unknown, PowerMode::External);
+        assert_eq!(render(&external), "VAL onewire 01-00080b6b123d external");
+        assert!(!parasitic.is_final());
+    }
+
+    #[test]
+    fn long_reasons_are_truncated() {
+        let token = "abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyz";
+        let err = str_to_command(token).unwrap_err();
+        let Response::Err(reason) = Response::error(&err) else {
+            panic!("expected an error response");
+        };
+        assert!(reason.starts_with("unknown command"));
+    }
+}
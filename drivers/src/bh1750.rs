@@ -0,0 +1,512 @@
+//! A driver for the BH1750FVI digital 16bit light sensor, see [Bh1750].
+//!
+//! The driver is generic over the [I2c] trait of `embedded-hal-async`, such that it runs on any
+//! board that provides an async I2C bus. A shared bus is supported by handing a `&mut` of the
+//! bus to the driver, as the trait is implemented for mutable references too.
+//!
+//! The resolution and the measurement time register (MTreg) are kept in a [Config]. A higher
+//! MTreg increases the sensitivity and the integration time, a lower one extends the range.
+//!
+//! A measurement takes up to 180ms in the H-resolution modes and 24ms in L-resolution mode for
+//! the default MTreg, the time scales with MTreg, see [Config::conversion_time_us]. The driver
+//! waits that long with a [DelayNs] before the result is read.
+//!
+//! Write Format: the BH1750FVI is not able to accept plural commands without stop condition,
+//! therefore every opcode is sent in its own write transaction.
+
+use core::fmt;
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::{Error as I2cError, I2c};
+
+use crate::error::{i2c_fault, BusFault};
+use crate::units::Lux;
+
+/// The address if the ADDR pin is high
+pub const BH1750_ADDR_H: u8 = 0x5C;
+
+/// The address if the ADDR pin is low
+pub const BH1750_ADDR_L: u8 = 0x23;
+
+const OPC_POWER_DOWN: u8 = 0b0000;
+const OPC_POWER_ON: u8 = 0b0001;
+const OPC_RESET: u8 = 0b0111;
+const OPC_CONTINUOUS: u8 = 0b01_0000;
+const OPC_ONE_TIME: u8 = 0b10_0000;
+const OPC_MTREG_HIGH: u8 = 0b0100_0000;
+const OPC_MTREG_LOW: u8 = 0b0110_0000;
+
+/// The smallest value of the measurement time register
+pub const MTREG_MIN: u8 = 31;
+
+/// The largest value of the measurement time register
+pub const MTREG_MAX: u8 = 254;
+
+/// The value of the measurement time register after power up, the datasheet sensitivity refers to it
+pub const MTREG_DEFAULT: u8 = 69;
+
+/// The resolution mode of a measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    /// H-resolution mode, 1 lx
+    #[default]
+    High,
+
+    /// H-resolution mode 2, 0.5 lx
+    High2,
+
+    /// L-resolution mode, 4 lx
+    Low,
+}
+
+impl Resolution {
+    /// The maximum measurement time for the default MTreg in microseconds according to the datasheet
+    pub fn max_conversion_time_us(&self) -> u32 {
+        match self {
+            Resolution::High | Resolution::High2 => 180_000,
+            Resolution::Low => 24_000,
+        }
+    }
+
+    /// The lower bits of the measurement opcodes
+    fn opcode_bits(&self) -> u8 {
+        match self {
+            Resolution::High => 0b00,
+            Resolution::High2 => 0b01,
+            Resolution::Low => 0b11,
+        }
+    }
+}
+
+/// The settings of the sensor that affect measurements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub resolution: Resolution,
+
+    /// the measurement time register in the range [MTREG_MIN]..=[MTREG_MAX]
+    pub mtreg: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            resolution: Resolution::High,
+            mtreg: MTREG_DEFAULT,
+        }
+    }
+}
+
+impl Config {
+    /// Converts a raw count measured with this config to [Lux]
+    ///
+    /// The datasheet gives `lx = count / 1.2` for the default MTreg, the count scales with MTreg
+    /// and H-resolution mode 2 counts twice per lux.
+    pub fn lux(&self, raw: u16) -> Lux {
+        let divisor = match self.resolution {
+            Resolution::High2 => 2,
+            Resolution::High | Resolution::Low => 1,
+        };
+        let numerator = raw as u64 * 1000 * 10 * MTREG_DEFAULT as u64;
+        let denominator = 12 * self.mtreg.max(1) as u64 * divisor;
+        Lux::from_millilux((numerator / denominator) as u32)
+    }
+
+    /// The time a measurement with this config takes at most, in microseconds
+    pub fn conversion_time_us(&self) -> u32 {
+        self.resolution.max_conversion_time_us() * self.mtreg as u32 / MTREG_DEFAULT as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bh1750Error<E> {
+    /// the bus transaction with the sensor at the given address failed
+    I2c { addr: u8, error: E },
+
+    /// the value is outside of [MTREG_MIN]..=[MTREG_MAX], nothing has been sent
+    InvalidMtreg(u8),
+}
+
+impl<E: I2cError> Bh1750Error<E> {
+    /// Gives the kind and address of a bus error, None if the bus has not been used
+    pub fn fault(&self) -> Option<BusFault> {
+        match self {
+            Bh1750Error::I2c { addr, error } => Some(i2c_fault(error, *addr)),
+            Bh1750Error::InvalidMtreg(_) => None,
+        }
+    }
+}
+
+impl<E: I2cError> fmt::Display for Bh1750Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bh1750Error::I2c { addr, error } => write!(f, "{}", i2c_fault(error, *addr)),
+            Bh1750Error::InvalidMtreg(mtreg) => {
+                write!(f, "MTreg {} is out of range {}..{}", mtreg, MTREG_MIN, MTREG_MAX)
+            }
+        }
+    }
+}
+
+/// The state of the sensor as it is known by the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bh1750State {
+    /// no active state, the sensor waits for the power on command
+    PowerDown,
+
+    /// the sensor waits for a measurement command
+    PowerOn,
+
+    /// the sensor measures continuously, see [Bh1750::read]
+    Continuous,
+}
+
+pub struct Bh1750<I2C> {
+    i2c: I2C,
+    addr: u8,
+    state: Bh1750State,
+    config: Config,
+}
+
+impl<I2C: I2c> Bh1750<I2C> {
+    /// Creates a driver for the sensor at the given address, the sensor is assumed to be powered down
+    pub fn new(i2c: I2C, addr: u8) -> Self {
+        Self::with_config(i2c, addr, Config::default())
+    }
+
+    /// Creates a driver for a sensor that has been configured before, e.g. by another driver instance
+    pub fn with_config(i2c: I2C, addr: u8, config: Config) -> Self {
+        Bh1750 {
+            i2c,
+            addr,
+            state: Bh1750State::PowerDown,
+            config,
+        }
+    }
+
+    pub fn addr(&self) -> u8 {
+        self.addr
+    }
+
+    pub fn state(&self) -> Bh1750State {
+        self.state
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Gives the bus back
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    pub async fn power_on(&mut self) -> Result<(), Bh1750Error<I2C::Error>> {
+        self.command(OPC_POWER_ON).await?;
+        self.state = Bh1750State::PowerOn;
+        Ok(())
+    }
+
+    pub async fn power_down(&mut self) -> Result<(), Bh1750Error<I2C::Error>> {
+        self.command(OPC_POWER_DOWN).await?;
+        self.state = Bh1750State::PowerDown;
+        Ok(())
+    }
+
+    /// Clears the data register, the reset is not accepted in power down, so the sensor is powered on first
+    pub async fn reset(&mut self) -> Result<(), Bh1750Error<I2C::Error>> {
+        if self.state == Bh1750State::PowerDown {
+            self.power_on().await?;
+        }
+        self.command(OPC_RESET).await
+    }
+
+    /// Selects the resolution of the following measurements, a continuous measurement is restarted
+    pub async fn set_resolution(&mut self, resolution: Resolution) -> Result<(), Bh1750Error<I2C::Error>> {
+        self.config.resolution = resolution;
+        if self.state == Bh1750State::Continuous {
+            self.command(OPC_CONTINUOUS | resolution.opcode_bits()).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the measurement time register, values outside [MTREG_MIN]..=[MTREG_MAX] are rejected
+    pub async fn set_mtreg(&mut self, mtreg: u8) -> Result<(), Bh1750Error<I2C::Error>> {
+        if !(MTREG_MIN..=MTREG_MAX).contains(&mtreg) {
+            return Err(Bh1750Error::InvalidMtreg(mtreg));
+        }
+        self.command(OPC_MTREG_HIGH | (mtreg >> 5)).await?;
+        self.command(OPC_MTREG_LOW | (mtreg & 0b1_1111)).await?;
+        self.config.mtreg = mtreg;
+        Ok(())
+    }
+
+    /// Measures once and gives the raw count, the sensor powers down afterwards
+    ///
+    /// The conversion time of the active mode and MTreg is awaited before the result is read.
+    pub async fn one_shot<D: DelayNs>(&mut self, delay: &mut D) -> Result<u16, Bh1750Error<I2C::Error>> {
+        self.power_on().await?;
+        self.command(OPC_ONE_TIME | self.config.resolution.opcode_bits()).await?;
+        delay.delay_us(self.config.conversion_time_us()).await;
+        let raw = self.read_raw().await?;
+        self.state = Bh1750State::PowerDown;
+        Ok(raw)
+    }
+
+    /// Starts the continuous measurement, the latest result is given by [Bh1750::read]
+    ///
+    /// The conversion time of the first measurement is awaited, such that it can be read right away.
+    pub async fn start_continuous<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), Bh1750Error<I2C::Error>> {
+        self.power_on().await?;
+        self.command(OPC_CONTINUOUS | self.config.resolution.opcode_bits()).await?;
+        self.state = Bh1750State::Continuous;
+        delay.delay_us(self.config.conversion_time_us()).await;
+        Ok(())
+    }
+
+    /// The time between two results of the continuous measurement, in microseconds
+    pub fn conversion_time_us(&self) -> u32 {
+        self.config.conversion_time_us()
+    }
+
+    /// Reads the raw count of the latest measurement, a new result is available every [Bh1750::conversion_time_us]
+    pub async fn read(&mut self) -> Result<u16, Bh1750Error<I2C::Error>> {
+        self.read_raw().await
+    }
+
+    async fn command(&mut self, opcode: u8) -> Result<(), Bh1750Error<I2C::Error>> {
+        let addr = self.addr;
+        self.i2c
+            .write(addr, &[opcode])
+            .await
+            .map_err(|error| Bh1750Error::I2c { addr, error })
+    }
+
+    async fn read_raw(&mut self) -> Result<u16, Bh1750Error<I2C::Error>> {
+        let mut buf = [0u8; 2];
+        let addr = self.addr;
+        self.i2c
+            .read(addr, &mut buf)
+            .await
+            .map_err(|error| Bh1750Error::I2c { addr, error })?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+    use std::vec;
+
+    use super::*;
+    use crate::error::BusFaultKind;
+    use embassy_futures::block_on;
+    use embedded_hal_async::i2c::{ErrorKind, NoAcknowledgeSource};
+    use embedded_hal_mock::eh1::delay::{self, CheckedDelay, NoopDelay};
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    const ADDR: u8 = BH1750_ADDR_L;
+
+    #[test]
+    fn power_on_and_down() {
+        let expectations = [
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![OPC_POWER_DOWN]),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), ADDR);
+
+        block_on(sensor.power_on()).unwrap();
+        assert_eq!(sensor.state(), Bh1750State::PowerOn);
+        block_on(sensor.power_down()).unwrap();
+        assert_eq!(sensor.state(), Bh1750State::PowerDown);
+        sensor.release().done();
+    }
+
+    #[test]
+    fn reset_powers_on_first() {
+        let expectations = [
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![OPC_RESET]),
+            Transaction::write(ADDR, vec![OPC_RESET]),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), ADDR);
+
+        block_on(sensor.reset()).unwrap();
+        block_on(sensor.reset()).unwrap();
+        assert_eq!(sensor.state(), Bh1750State::PowerOn);
+        sensor.release().done();
+    }
+
+    #[test]
+    fn one_shot() {
+        let expectations = [
+            Transaction::write(BH1750_ADDR_H, vec![OPC_POWER_ON]),
+            Transaction::write(BH1750_ADDR_H, vec![0x20]),
+            Transaction::read(BH1750_ADDR_H, vec![0x83, 0x90]),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), BH1750_ADDR_H);
+        let mut delay = CheckedDelay::new(&[delay::Transaction::async_delay_us(180_000)]);
+
+        assert_eq!(block_on(sensor.one_shot(&mut delay)), Ok(0x8390));
+        assert_eq!(sensor.state(), Bh1750State::PowerDown);
+        sensor.release().done();
+        delay.done();
+    }
+
+    #[test]
+    fn continuous() {
+        let expectations = [
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![0x10]),
+            Transaction::read(ADDR, vec![0x00, 0x01]),
+            Transaction::read(ADDR, vec![0xFF, 0xFF]),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), ADDR);
+
+        let mut delay = CheckedDelay::new(&[delay::Transaction::async_delay_us(180_000)]);
+        block_on(sensor.start_continuous(&mut delay)).unwrap();
+        assert_eq!(sensor.state(), Bh1750State::Continuous);
+        delay.done();
+        assert_eq!(block_on(sensor.read()), Ok(1));
+        assert_eq!(block_on(sensor.read()), Ok(u16::MAX));
+        sensor.release().done();
+    }
+
+    #[test]
+    fn resolution_modes() {
+        let expectations = [
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![0x21]),
+            Transaction::read(ADDR, vec![0x00, 0x10]),
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![0x13]),
+            Transaction::write(ADDR, vec![0x10]),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), ADDR);
+
+        block_on(sensor.set_resolution(Resolution::High2)).unwrap();
+        assert_eq!(block_on(sensor.one_shot(&mut NoopDelay)), Ok(0x10));
+        block_on(sensor.set_resolution(Resolution::Low)).unwrap();
+        block_on(sensor.start_continuous(&mut NoopDelay)).unwrap();
+        block_on(sensor.set_resolution(Resolution::High)).unwrap();
+        assert_eq!(sensor.config().resolution, Resolution::High);
+        sensor.release().done();
+    }
+
+    #[test]
+    fn mtreg() {
+        // the datasheet example 0x45 and the limits
+        let expectations = [
+            Transaction::write(ADDR, vec![0b0100_0010]),
+            Transaction::write(ADDR, vec![0b0110_0101]),
+            Transaction::write(ADDR, vec![0b0100_0000]),
+            Transaction::write(ADDR, vec![0b0111_1111]),
+            Transaction::write(ADDR, vec![0b0100_0111]),
+            Transaction::write(ADDR, vec![0b0111_1110]),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), ADDR);
+
+        block_on(sensor.set_mtreg(MTREG_DEFAULT)).unwrap();
+        assert_eq!(sensor.config().mtreg, 0x45);
+        block_on(sensor.set_mtreg(MTREG_MIN)).unwrap();
+        block_on(sensor.set_mtreg(MTREG_MAX)).unwrap();
+        assert_eq!(sensor.config().mtreg, MTREG_MAX);
+        assert_eq!(block_on(sensor.set_mtreg(30)), Err(Bh1750Error::InvalidMtreg(30)));
+        assert_eq!(block_on(sensor.set_mtreg(255)), Err(Bh1750Error::InvalidMtreg(255)));
+        assert_eq!(sensor.config().mtreg, MTREG_MAX);
+        sensor.release().done();
+    }
+
+    #[test]
+    fn lux_conversion() {
+        let default = Config::default();
+        assert_eq!(default.lux(0).millilux(), 0);
+        // the datasheet examples, 0x8390 counts are 28067 lx and twice the MTreg halves the lux
+        assert_eq!(default.lux(0x8390).millilux(), 28_066_666);
+        assert_eq!(default.lux(u16::MAX).millilux(), 54_612_500);
+
+        let hr2 = Config {
+            resolution: Resolution::High2,
+            ..default
+        };
+        assert_eq!(hr2.lux(0x8390).millilux(), 14_033_333);
+
+        let sensitive = Config {
+            mtreg: 138,
+            ..default
+        };
+        assert_eq!(sensitive.lux(1200).millilux(), 500_000);
+        let wide = Config {
+            mtreg: MTREG_MIN,
+            ..default
+        };
+        assert_eq!(wide.lux(u16::MAX).millilux(), 121_556_854);
+    }
+
+    #[test]
+    fn conversion_times() {
+        let mut config = Config::default();
+        assert_eq!(config.conversion_time_us(), 180_000);
+        config.resolution = Resolution::Low;
+        assert_eq!(config.conversion_time_us(), 24_000);
+        config.mtreg = MTREG_MAX;
+        assert_eq!(config.conversion_time_us(), 88_347);
+        config.resolution = Resolution::High2;
+        config.mtreg = MTREG_MIN;
+        assert_eq!(config.conversion_time_us(), 80_869);
+    }
+
+    #[test]
+    fn one_shot_waits_for_the_mtreg_scaled_conversion() {
+        let expectations = [
+            Transaction::write(ADDR, vec![0b0100_0100]),
+            Transaction::write(ADDR, vec![0b0110_1010]),
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![0x23]),
+            Transaction::read(ADDR, vec![0x00, 0x02]),
+        ];
+        let mut sensor = Bh1750::with_config(
+            Mock::new(&expectations),
+            ADDR,
+            Config {
+                resolution: Resolution::Low,
+                mtreg: MTREG_DEFAULT,
+            },
+        );
+        let mut delay = CheckedDelay::new(&[delay::Transaction::async_delay_us(48_000)]);
+
+        block_on(sensor.set_mtreg(138)).unwrap();
+        assert_eq!(block_on(sensor.one_shot(&mut delay)), Ok(2));
+        sensor.release().done();
+        delay.done();
+    }
+
+    #[test]
+    fn bus_errors_keep_the_state() {
+        let expectations = [
+            Transaction::write(ADDR, vec![OPC_POWER_ON]),
+            Transaction::write(ADDR, vec![0x10]).with_error(ErrorKind::Other),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), ADDR);
+
+        let err = block_on(sensor.start_continuous(&mut NoopDelay)).unwrap_err();
+        assert_eq!(err, Bh1750Error::I2c { addr: ADDR, error: ErrorKind::Other });
+        assert_eq!(sensor.state(), Bh1750State::PowerOn);
+        sensor.release().done();
+    }
+
+    #[test]
+    fn errors_name_kind_and_address() {
+        let expectations = [
+            Transaction::write(BH1750_ADDR_H, vec![OPC_POWER_ON])
+                .with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+        ];
+        let mut sensor = Bh1750::new(Mock::new(&expectations), BH1750_ADDR_H);
+
+        let err = block_on(sensor.one_shot(&mut NoopDelay)).unwrap_err();
+        assert_eq!(err.fault(), Some(BusFault { kind: BusFaultKind::Nack, addr: BH1750_ADDR_H }));
+        assert_eq!(err.to_string(), "NACK @0x5C");
+        assert_eq!(Bh1750Error::<ErrorKind>::InvalidMtreg(7).to_string(), "MTreg 7 is out of range 31..254");
+        sensor.release().done();
+    }
+}
@@ -0,0 +1,606 @@
+//! A driver for the DS18B20 digital thermometer on a 1-Wire bus, see [Ds18b20].
+//!
+//! The driver keeps the ROM of the sensor only and borrows the [OneWire] master for every
+//! transaction, such that the bus can be shared with other slaves and is not locked during the
+//! conversion. A sensor that is the only slave on the bus may be addressed with Skip ROM, see
+//! [Ds18b20::single].
+//!
+//! A measurement is started with Convert T and takes up to 750ms in the 12 bit resolution of the
+//! power up state, see [CONVERSION_TIME_US]. The result is read with Read Scratchpad, the nine
+//! bytes are validated with their CRC-8 and the temperature is given in [Celsius].
+//!
+//! The [Config] holds the resolution and the alarm limits:
+//!
+//! | resolution | step      | conversion time |
+//! |------------|-----------|-----------------|
+//! | 9 bit      | 0.5 °C    | 93.75ms         |
+//! | 10 bit     | 0.25 °C   | 187.5ms         |
+//! | 11 bit     | 0.125 °C  | 375ms           |
+//! | 12 bit     | 0.0625 °C | 750ms           |
+//!
+//! It is written with Write Scratchpad and persisted in the EEPROM with Copy Scratchpad, see
+//! [Ds18b20::configure]. A sensor whose last conversion is at or beyond one of the alarm limits
+//! takes part in an Alarm Search, see [Search::alarms](crate::onewire::Search::alarms).
+//!
+//! A parasite powered sensor draws the current for a conversion or an EEPROM write from the data
+//! line, the master powers it with the [StrongPullUp] meanwhile and the bus is blocked. The power
+//! mode is detected with Read Power Supply, see [Ds18b20::read_power_supply].
+
+use embedded_hal::delay::DelayNs as BlockingDelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_async::delay::DelayNs;
+
+use crate::onewire::{crc8, OneWire, OneWireError, PowerMode, Rom, StrongPullUp};
+use crate::units::Celsius;
+
+/// The family code of the DS18B20 in the first byte of its ROM
+pub const FAMILY_CODE: u8 = 0x28;
+
+/// Starts a temperature conversion
+pub const CONVERT_T: u8 = 0x44;
+
+/// Reads the nine bytes of the scratchpad, the last one is the CRC
+pub const READ_SCRATCHPAD: u8 = 0xBE;
+
+/// Writes TH, TL and the configuration register to the scratchpad
+pub const WRITE_SCRATCHPAD: u8 = 0x4E;
+
+/// Copies TH, TL and the configuration register from the scratchpad to the EEPROM
+pub const COPY_SCRATCHPAD: u8 = 0x48;
+
+/// The maximum time of a conversion in 12 bit resolution in microseconds
+pub const CONVERSION_TIME_US: u32 = 750_000;
+
+/// The maximum time the EEPROM takes to store a copy of the scratchpad in microseconds
+pub const COPY_TIME_US: u32 = 10_000;
+
+/// The lowest temperature the sensor measures, used as lower alarm limit by default
+pub const TEMPERATURE_MIN: i8 = -55;
+
+/// The highest temperature the sensor measures, used as upper alarm limit by default
+pub const TEMPERATURE_MAX: i8 = 125;
+
+/// The temperature register holds this value after power up until the first conversion
+pub const POWER_UP_TEMPERATURE: Celsius = Celsius::from_degrees(85);
+
+/// The resolution of a conversion, a higher one takes longer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    /// 0.5 °C
+    Bits9,
+
+    /// 0.25 °C
+    Bits10,
+
+    /// 0.125 °C
+    Bits11,
+
+    /// 0.0625 °C, the resolution after power up
+    #[default]
+    Bits12,
+}
+
+impl Resolution {
+    /// The resolution with the given number of bits, None outside of 9..=12
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            9 => Some(Resolution::Bits9),
+            10 => Some(Resolution::Bits10),
+            11 => Some(Resolution::Bits11),
+            12 => Some(Resolution::Bits12),
+            _ => None,
+        }
+    }
+
+    pub fn bits(&self) -> u8 {
+        match self {
+            Resolution::Bits9 => 9,
+            Resolution::Bits10 => 10,
+            Resolution::Bits11 => 11,
+            Resolution::Bits12 => 12,
+        }
+    }
+
+    /// The maximum conversion time in microseconds according to the datasheet, it halves per bit
+    pub fn conversion_time_us(&self) -> u32 {
+        CONVERSION_TIME_US >> (12 - self.bits())
+    }
+
+    /// The configuration register, R1 and R0 in bits 6 and 5, the lower bits read as 1
+    fn register(&self) -> u8 {
+        ((self.bits() - 9) << 5) | 0b1_1111
+    }
+
+    fn from_register(register: u8) -> Self {
+        match (register >> 5) & 0b11 {
+            0 => Resolution::Bits9,
+            1 => Resolution::Bits10,
+            2 => Resolution::Bits11,
+            _ => Resolution::Bits12,
+        }
+    }
+}
+
+/// The settings of the sensor that are persisted in its EEPROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub resolution: Resolution,
+
+    /// the lower alarm limit in whole °C
+    pub tl: i8,
+
+    /// the upper alarm limit in whole °C
+    pub th: i8,
+}
+
+impl Default for Config {
+    /// The power up resolution, the alarm limits are at the ends of the range and never trigger
+    fn default() -> Self {
+        Config {
+            resolution: Resolution::Bits12,
+            tl: TEMPERATURE_MIN,
+            th: TEMPERATURE_MAX,
+        }
+    }
+}
+
+impl Config {
+    /// The time a conversion with this config takes at most, in microseconds
+    pub fn conversion_time_us(&self) -> u32 {
+        self.resolution.conversion_time_us()
+    }
+}
+
+/// The memory of the sensor as read by [Ds18b20::read_scratchpad]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scratchpad(pub [u8; 9]);
+
+impl Scratchpad {
+    /// Indicates if the CRC in the last byte matches the others and the reserved byte 5 is 0xFF
+    ///
+    /// A bus that is stuck low reads as zeros, whose CRC matches as well, the reserved byte tells
+    /// them apart. A bus that is stuck high reads as ones, whose CRC matches too.
+    pub fn is_valid(&self) -> bool {
+        let stuck_high = self.0.iter().all(|byte| *byte == 0xFF);
+        !stuck_high && self.0[5] == 0xFF && crc8(&self.0[..8]) == self.0[8]
+    }
+
+    /// The temperature register in 1/16 °C, the bits below the resolution are undefined
+    pub fn raw_temperature(&self) -> i16 {
+        i16::from_le_bytes([self.0[0], self.0[1]])
+    }
+
+    /// The temperature in the resolution of the configuration register
+    pub fn temperature(&self) -> Celsius {
+        let undefined = (1i16 << (12 - self.config().resolution.bits())) - 1;
+        let raw = self.raw_temperature() & !undefined;
+        Celsius::from_millidegrees(raw as i32 * 125 / 2)
+    }
+
+    /// The upper alarm trigger register
+    pub fn th(&self) -> i8 {
+        self.0[2] as i8
+    }
+
+    /// The lower alarm trigger register
+    pub fn tl(&self) -> i8 {
+        self.0[3] as i8
+    }
+
+    /// The configuration register, bits 5 and 6 select the resolution
+    pub fn config_register(&self) -> u8 {
+        self.0[4]
+    }
+
+    pub fn config(&self) -> Config {
+        Config {
+            resolution: Resolution::from_register(self.config_register()),
+            tl: self.tl(),
+            th: self.th(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ds18b20 {
+    rom: Option<Rom>,
+    config: Config,
+    power: PowerMode,
+}
+
+impl Ds18b20 {
+    /// Creates a driver for the sensor with the given ROM, it is selected with Match ROM
+    pub fn new(rom: Rom) -> Self {
+        Self::with_config(Some(rom), Config::default())
+    }
+
+    /// Creates a driver for the only slave on the bus, it is selected with Skip ROM
+    pub fn single() -> Self {
+        Self::with_config(None, Config::default())
+    }
+
+    /// Creates a driver for a sensor that has been configured before, e.g. by another driver instance
+    ///
+    /// The sensor is assumed to be externally powered until [Ds18b20::read_power_supply] tells otherwise.
+    pub fn with_config(rom: Option<Rom>, config: Config) -> Self {
+        Ds18b20 {
+            rom,
+            config,
+            power: PowerMode::External,
+        }
+    }
+
+    /// The ROM of the sensor, None if it is addressed with Skip ROM
+    pub fn rom(&self) -> Option<Rom> {
+        self.rom
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// The time a conversion in the configured resolution takes at most, in microseconds
+    pub fn conversion_time_us(&self) -> u32 {
+        self.config.conversion_time_us()
+    }
+
+    pub fn power_mode(&self) -> PowerMode {
+        self.power
+    }
+
+    /// Asks the sensor how it is powered, a Skip ROM driver gives parasitic if any slave is
+    pub async fn read_power_supply<P, D>(&mut self, bus: &mut OneWire<P, D>) -> Result<PowerMode, OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin,
+        D: DelayNs + BlockingDelayNs,
+    {
+        self.select(bus).await?;
+        self.power = bus.read_power_supply().await?;
+        Ok(self.power)
+    }
+
+    /// Writes the config to the scratchpad, it is lost on power down unless it is copied to the EEPROM
+    pub async fn write_config<P, D>(&mut self, bus: &mut OneWire<P, D>, config: Config) -> Result<(), OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin,
+        D: DelayNs + BlockingDelayNs,
+    {
+        self.select(bus).await?;
+        bus.write_bytes(&[WRITE_SCRATCHPAD, config.th as u8, config.tl as u8, config.resolution.register()])
+            .await?;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Copies the config of the scratchpad to the EEPROM and waits [COPY_TIME_US] for it
+    ///
+    /// A parasite powered sensor is powered by the strong pull-up of the bus meanwhile.
+    pub async fn copy_scratchpad<P, D, W>(&self, bus: &mut OneWire<P, D>, delay: &mut W) -> Result<(), OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin + StrongPullUp,
+        D: DelayNs + BlockingDelayNs,
+        W: DelayNs,
+    {
+        self.select(bus).await?;
+        match self.power {
+            PowerMode::External => {
+                bus.write_byte(COPY_SCRATCHPAD).await?;
+                delay.delay_us(COPY_TIME_US).await;
+                Ok(())
+            }
+            PowerMode::Parasitic => bus.write_byte_powered(COPY_SCRATCHPAD, COPY_TIME_US).await,
+        }
+    }
+
+    /// Detects the power mode, writes the config and persists it, such that it survives a power down
+    pub async fn configure<P, D, W>(
+        &mut self,
+        bus: &mut OneWire<P, D>,
+        delay: &mut W,
+        config: Config,
+    ) -> Result<(), OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin + StrongPullUp,
+        D: DelayNs + BlockingDelayNs,
+        W: DelayNs,
+    {
+        self.read_power_supply(bus).await?;
+        self.write_config(bus, config).await?;
+        self.copy_scratchpad(bus, delay).await
+    }
+
+    /// Starts a conversion, gives the microseconds until the result may be read
+    ///
+    /// That is [Ds18b20::conversion_time_us] for an externally powered sensor, the bus is free
+    /// meanwhile. A parasite powered sensor is powered by the strong pull-up for the whole
+    /// conversion instead, the result may be read right away.
+    pub async fn start_conversion<P, D>(&self, bus: &mut OneWire<P, D>) -> Result<u32, OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin + StrongPullUp,
+        D: DelayNs + BlockingDelayNs,
+    {
+        self.select(bus).await?;
+        match self.power {
+            PowerMode::External => {
+                bus.write_byte(CONVERT_T).await?;
+                Ok(self.conversion_time_us())
+            }
+            PowerMode::Parasitic => {
+                bus.write_byte_powered(CONVERT_T, self.conversion_time_us()).await?;
+                Ok(0)
+            }
+        }
+    }
+
+    /// Reads the scratchpad, a CRC mismatch or a stuck bus gives [OneWireError::Crc]
+    ///
+    /// The config of the driver is taken over from the scratchpad, such that the following
+    /// conversions are awaited in the resolution of the sensor.
+    pub async fn read_scratchpad<P, D>(&mut self, bus: &mut OneWire<P, D>) -> Result<Scratchpad, OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin,
+        D: DelayNs + BlockingDelayNs,
+    {
+        self.select(bus).await?;
+        bus.write_byte(READ_SCRATCHPAD).await?;
+        let mut scratchpad = Scratchpad([0; 9]);
+        bus.read_bytes(&mut scratchpad.0).await?;
+        if !scratchpad.is_valid() {
+            return Err(OneWireError::Crc);
+        }
+        self.config = scratchpad.config();
+        Ok(scratchpad)
+    }
+
+    /// Reads the result of the latest conversion
+    pub async fn read_temperature<P, D>(&mut self, bus: &mut OneWire<P, D>) -> Result<Celsius, OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin,
+        D: DelayNs + BlockingDelayNs,
+    {
+        Ok(self.read_scratchpad(bus).await?.temperature())
+    }
+
+    /// Detects the power mode, converts and reads the temperature, the bus is blocked for the
+    /// whole conversion time
+    ///
+    /// Use [Ds18b20::start_conversion] and [Ds18b20::read_temperature] to share the bus meanwhile.
+    pub async fn measure<P, D, W>(&mut self, bus: &mut OneWire<P, D>, delay: &mut W) -> Result<Celsius, OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin + StrongPullUp,
+        D: DelayNs + BlockingDelayNs,
+        W: DelayNs,
+    {
+        self.read_power_supply(bus).await?;
+        let wait_us = self.start_conversion(bus).await?;
+        if wait_us > 0 {
+            delay.delay_us(wait_us).await;
+        }
+        self.read_temperature(bus).await
+    }
+
+    async fn select<P, D>(&self, bus: &mut OneWire<P, D>) -> Result<(), OneWireError<P::Error>>
+    where
+        P: InputPin + OutputPin,
+        D: DelayNs + BlockingDelayNs,
+    {
+        match &self.rom {
+            Some(rom) => bus.match_rom(rom).await,
+            None => bus.skip_rom().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec;
+
+    use super::*;
+    use crate::onewire::sim::{master, Slave};
+    use crate::onewire::READ_POWER_SUPPLY;
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::delay::{self, CheckedDelay};
+
+    const ROM_A: [u8; 8] = [0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x34];
+    const ROM_B: [u8; 8] = [0x28, 0x3D, 0x12, 0x6B, 0x0B, 0x00, 0x00, 0x83];
+
+    /// A scratchpad in 12 bit resolution with a valid CRC
+    fn scratchpad(raw: i16) -> [u8; 9] {
+        scratchpad_with(raw, Resolution::Bits12)
+    }
+
+    fn scratchpad_with(raw: i16, resolution: Resolution) -> [u8; 9] {
+        let [lsb, msb] = raw.to_le_bytes();
+        let mut data = [lsb, msb, 0x4B, 0x46, resolution.register(), 0xFF, 0x0C, 0x10, 0];
+        data[8] = crc8(&data[..8]);
+        data
+    }
+
+    #[test]
+    fn datasheet_temperatures() {
+        let cases = [
+            (0x07D0, "125.00"),
+            (0x0550, "85.00"),
+            (0x0191, "25.06"),
+            (0x00A2, "10.12"),
+            (0x0008, "0.50"),
+            (0x0000, "0.00"),
+            (-0x0008, "-0.50"),
+            (-0x00A2, "-10.12"),
+            (-0x0191, "-25.06"),
+            (-0x0370, "-55.00"),
+        ];
+        for (raw, text) in cases {
+            let temperature = Scratchpad(scratchpad(raw)).temperature();
+            assert_eq!(std::format!("{}", temperature), text, "{:#06x}", raw);
+        }
+        assert_eq!(Scratchpad(scratchpad(0x0550)).temperature(), POWER_UP_TEMPERATURE);
+        assert_eq!(Scratchpad(scratchpad(0x0191)).temperature(), Celsius::from_millidegrees(25_062));
+    }
+
+    #[test]
+    fn registers() {
+        let pad = Scratchpad(scratchpad(0x0191));
+        assert!(pad.is_valid());
+        assert_eq!(pad.raw_temperature(), 0x0191);
+        assert_eq!((pad.th(), pad.tl(), pad.config_register()), (75, 70, 0x7F));
+        let config = Config { resolution: Resolution::Bits12, tl: 70, th: 75 };
+        assert_eq!(pad.config(), config);
+    }
+
+    #[test]
+    fn resolutions() {
+        let cases = [
+            (Resolution::Bits9, 0x1F, 93_750),
+            (Resolution::Bits10, 0x3F, 187_500),
+            (Resolution::Bits11, 0x5F, 375_000),
+            (Resolution::Bits12, 0x7F, 750_000),
+        ];
+        for (resolution, register, time) in cases {
+            assert_eq!(resolution.register(), register);
+            assert_eq!(Resolution::from_register(register), resolution);
+            assert_eq!(Resolution::from_bits(resolution.bits()), Some(resolution));
+            assert_eq!(resolution.conversion_time_us(), time);
+        }
+        assert_eq!(Resolution::from_bits(8), None);
+        assert_eq!(Resolution::from_bits(13), None);
+    }
+
+    #[test]
+    fn undefined_bits_are_masked() {
+        // 25.0625 °C with all undefined bits set
+        let cases = [
+            (Resolution::Bits9, 25_000, -25_500),
+            (Resolution::Bits10, 25_000, -25_250),
+            (Resolution::Bits11, 25_000, -25_125),
+            (Resolution::Bits12, 25_062, -25_062),
+        ];
+        for (resolution, positive, negative) in cases {
+            let pad = Scratchpad(scratchpad_with(0x0191, resolution));
+            assert_eq!(pad.temperature(), Celsius::from_millidegrees(positive), "{:?}", resolution);
+            let pad = Scratchpad(scratchpad_with(-0x0191, resolution));
+            assert_eq!(pad.temperature(), Celsius::from_millidegrees(negative), "{:?}", resolution);
+        }
+    }
+
+    #[test]
+    fn configure() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[])]);
+        let mut sensor = Ds18b20::new(Rom(ROM_A));
+        let config = Config { resolution: Resolution::Bits10, tl: -10, th: 30 };
+        let mut delay = CheckedDelay::new(&[delay::Transaction::async_delay_us(COPY_TIME_US)]);
+
+        block_on(sensor.configure(&mut bus, &mut delay, config)).unwrap();
+        assert_eq!(sensor.config(), config);
+        assert_eq!(sensor.conversion_time_us(), 187_500);
+        assert_eq!(
+            sim.borrow().slaves[0].received,
+            vec![READ_POWER_SUPPLY, WRITE_SCRATCHPAD, 30, -10i8 as u8, 0x3F, COPY_SCRATCHPAD]
+        );
+        assert!(sim.borrow().strong_pull_ups.is_empty());
+        delay.done();
+    }
+
+    #[test]
+    fn parasite_powered_sensor() {
+        let mut slave = Slave::new(ROM_A, &scratchpad(0x0191));
+        slave.parasitic = true;
+        let (mut bus, sim) = master(vec![slave]);
+        let mut sensor = Ds18b20::new(Rom(ROM_A));
+        assert_eq!(sensor.power_mode(), PowerMode::External);
+        // the strong pull-up of the bus takes the place of the delay
+        let mut delay = CheckedDelay::new(&[]);
+
+        let config = Config { resolution: Resolution::Bits11, ..Config::default() };
+        block_on(sensor.configure(&mut bus, &mut delay, config)).unwrap();
+        assert_eq!(sensor.power_mode(), PowerMode::Parasitic);
+        let temperature = block_on(sensor.measure(&mut bus, &mut delay)).unwrap();
+        assert_eq!(temperature, Celsius::from_millidegrees(25_062));
+        assert_eq!(sim.borrow().strong_pull_ups, vec![COPY_TIME_US as u64, 375_000]);
+        delay.done();
+    }
+
+    #[test]
+    fn conversion_time_follows_the_sensor() {
+        let data = scratchpad_with(0x0191, Resolution::Bits9);
+        let (mut bus, _) = master(vec![Slave::new(ROM_A, &data)]);
+        let mut sensor = Ds18b20::single();
+        let mut delay = CheckedDelay::new(&[
+            delay::Transaction::async_delay_us(CONVERSION_TIME_US),
+            delay::Transaction::async_delay_us(93_750),
+        ]);
+
+        // the first conversion is awaited in the power up resolution, the scratchpad tells better
+        assert_eq!(block_on(sensor.measure(&mut bus, &mut delay)), Ok(Celsius::from_degrees(25)));
+        assert_eq!(sensor.config().resolution, Resolution::Bits9);
+        assert_eq!(block_on(sensor.measure(&mut bus, &mut delay)), Ok(Celsius::from_degrees(25)));
+        delay.done();
+    }
+
+    #[test]
+    fn measure_single_sensor() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &scratchpad(0x0191))]);
+        let mut sensor = Ds18b20::single();
+        let mut delay = CheckedDelay::new(&[delay::Transaction::async_delay_us(CONVERSION_TIME_US)]);
+
+        let temperature = block_on(sensor.measure(&mut bus, &mut delay)).unwrap();
+        assert_eq!(temperature, Celsius::from_millidegrees(25_062));
+        assert_eq!(sim.borrow().slaves[0].received, vec![READ_POWER_SUPPLY, CONVERT_T, READ_SCRATCHPAD]);
+        delay.done();
+    }
+
+    #[test]
+    fn match_rom_selects_one_of_many() {
+        let (mut bus, sim) = master(vec![
+            Slave::new(ROM_A, &scratchpad(0x00A2)),
+            Slave::new(ROM_B, &scratchpad(-0x0191)),
+        ]);
+        let mut sensor = Ds18b20::new(Rom(ROM_B));
+        assert_eq!(sensor.rom(), Some(Rom(ROM_B)));
+
+        assert_eq!(block_on(sensor.start_conversion(&mut bus)), Ok(CONVERSION_TIME_US));
+        let temperature = block_on(sensor.read_temperature(&mut bus)).unwrap();
+        assert_eq!(temperature, Celsius::from_millidegrees(-25_062));
+        let sim = sim.borrow();
+        assert!(sim.slaves[0].received.is_empty());
+        assert_eq!(sim.slaves[1].received, vec![CONVERT_T, READ_SCRATCHPAD]);
+    }
+
+    #[test]
+    fn corrupted_scratchpad() {
+        let mut data = scratchpad(0x0191);
+        data[0] ^= 0x01;
+        let (mut bus, _) = master(vec![Slave::new(ROM_A, &data)]);
+
+        let result = block_on(Ds18b20::single().read_temperature(&mut bus));
+        assert_eq!(result, Err(OneWireError::Crc));
+    }
+
+    #[test]
+    fn shorted_bus() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &scratchpad(0x0191))]);
+        sim.borrow_mut().shorted = true;
+        let config = Config { resolution: Resolution::Bits10, tl: 20, th: 30 };
+        let mut sensor = Ds18b20::with_config(None, config);
+
+        // the scratchpad reads as zeros with a matching CRC, the config is kept
+        assert_eq!(block_on(sensor.read_temperature(&mut bus)), Err(OneWireError::Crc));
+        assert_eq!(sensor.config(), config);
+        assert!(!Scratchpad([0x00; 9]).is_valid());
+        assert!(!Scratchpad([0xFF; 9]).is_valid());
+
+        // a matching CRC without the reserved byte is no scratchpad either
+        let mut data = scratchpad(0x0191);
+        data[5] = 0x00;
+        data[8] = crc8(&data[..8]);
+        assert!(!Scratchpad(data).is_valid());
+    }
+
+    #[test]
+    fn no_sensor() {
+        let (mut bus, _) = master(vec![]);
+        let result = block_on(Ds18b20::single().start_conversion(&mut bus));
+        assert_eq!(result, Err(OneWireError::NoPresence));
+    }
+}
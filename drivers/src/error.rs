@@ -0,0 +1,34 @@
+//! Bus errors reduced to what is shown to the operator, see [BusFault] and [OneWireFault].
+//!
+//! The faults are types of `nucleo_types`, such that the console shares them without depending
+//! on the drivers. The I2C errors of any HAL are classified by [i2c_fault].
+
+use embedded_hal_async::i2c::{Error as I2cError, ErrorKind};
+
+pub use nucleo_types::error::{BusFault, BusFaultKind, OneWireFault, ParseBusFaultError, ParseOneWireFaultError};
+
+/// Classifies an I2C error of any HAL by its [ErrorKind]
+pub fn i2c_fault<E: I2cError>(error: &E, addr: u8) -> BusFault {
+    let kind = match error.kind() {
+        ErrorKind::NoAcknowledge(_) => BusFaultKind::Nack,
+        ErrorKind::ArbitrationLoss => BusFaultKind::ArbitrationLoss,
+        ErrorKind::Bus => BusFaultKind::Bus,
+        ErrorKind::Overrun => BusFaultKind::Overrun,
+        _ => BusFaultKind::Other,
+    };
+    BusFault { kind, addr }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal_async::i2c::NoAcknowledgeSource;
+
+    #[test]
+    fn from_i2c_error_kind() {
+        let nack = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+        assert_eq!(i2c_fault(&nack, 0x23), BusFault { kind: BusFaultKind::Nack, addr: 0x23 });
+        assert_eq!(i2c_fault(&ErrorKind::ArbitrationLoss, 0x5C).kind, BusFaultKind::ArbitrationLoss);
+        assert_eq!(i2c_fault(&ErrorKind::Other, 0x5C).kind, BusFaultKind::Other);
+    }
+}
@@ -0,0 +1,31 @@
+#![no_std]
+
+//! This crate contains the board independent drivers of the sensor platform.
+//!
+//! The drivers are generic over the traits of `embedded-hal-async` and are tested on the host
+//! against `embedded-hal-mock` or a simulated bus:
+//!
+//! - [x] BH1750FVI light sensor on I2C
+//! - [x] Bus errors that name their kind and device, e.g. `NACK @0x23`
+//! - [x] Scanning an I2C bus for devices, known parts are named
+//! - [x] 1-Wire bus master on an open-drain pin with the ROM commands and the ROM search
+//! - [x] Parasite powered slaves, detected with Read Power Supply and powered by a strong pull-up
+//! - [x] DS18B20 temperature sensor on 1-Wire, the scratchpad is validated with its CRC
+//!
+//! The units and faults they give are the wire-level types of `nucleo_types`, they are re-exported
+//! here, e.g. [Lux](units::Lux) and [Celsius](units::Celsius).
+
+pub mod bh1750;
+pub mod ds18b20;
+pub mod error;
+pub mod onewire;
+pub mod scan;
+
+pub use nucleo_types::units;
+
+pub use bh1750::{Bh1750, Bh1750Error, Bh1750State, Config as Bh1750Config, Resolution};
+pub use ds18b20::{Ds18b20, Scratchpad};
+pub use error::{BusFault, BusFaultKind, OneWireFault};
+pub use onewire::{crc8, known_family, OneWire, OneWireError, PowerMode, Rom, Search, StrongPullUp};
+pub use scan::{known_device, scan, Devices, ScanError};
+pub use units::{Celsius, Lux};
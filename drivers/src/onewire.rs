@@ -0,0 +1,993 @@
+//! A 1-Wire bus master on a single open-drain pin, see [OneWire].
+//!
+//! The master is generic over the digital pin traits of `embedded-hal` and a delay that
+//! implements the [DelayNs] traits of both `embedded-hal` and `embedded-hal-async`. The pin has
+//! to be open-drain with a pull-up of about 4.7k, a low drives the bus and a high releases it,
+//! such that the slaves can pull it low.
+//!
+//! The timing follows the recommended standard speed values of Maxim application note 126:
+//!
+//! | slot            | low      | then released                       |
+//! |-----------------|----------|-------------------------------------|
+//! | reset/presence  | 480µs    | sample after 70µs, then wait 410µs  |
+//! | write 1         | 6µs      | 64µs                                |
+//! | write 0         | 60µs     | 10µs                                |
+//! | read            | 6µs      | sample after 9µs, then wait 55µs    |
+//!
+//! A slot must not be stretched by more than a few microseconds, so each bit runs in a critical
+//! section and is timed by the blocking [DelayNs](BlockingDelayNs), e.g. a busy wait on the cycle
+//! counter of the core. Only the long waits of a reset and of the strong pull-up use the async
+//! [DelayNs], other tasks run meanwhile. Between two bytes the master yields to the other tasks,
+//! such that a transaction blocks them for the 0.6ms of a byte at most.
+//!
+//! A transaction may be dropped at one of its async waits, e.g. by a select with a timeout. The
+//! bus is released and the strong pull-up ends then, the slaves start over with the next reset.
+//!
+//! Every transaction starts with a reset followed by a ROM command that selects the slaves:
+//!
+//! - [x] Read ROM, the ROM of the only slave on the bus, see [OneWire::read_rom]
+//! - [x] Match ROM, selects the slave with the given ROM, see [OneWire::match_rom]
+//! - [x] Skip ROM, selects all slaves, see [OneWire::skip_rom]
+//! - [x] Search ROM and Alarm Search, enumerate the slaves, see [OneWire::search]
+//!
+//! A ROM that is read from the bus is validated with the Dallas/Maxim CRC-8, see [crc8].
+//!
+//! Slaves with two wires only, data and ground, are parasite powered: they charge a capacitor
+//! while the bus is high. That is not enough for a temperature conversion or an EEPROM write,
+//! during those the master has to drive the bus high with a [StrongPullUp]:
+//!
+//! - [x] Read Power Supply, tells if the selected slaves are parasite powered, see
+//!   [OneWire::power_mode]
+//! - [x] Strong pull-up after a function command, see [OneWire::write_byte_powered]
+
+use core::fmt;
+
+use embedded_hal::delay::DelayNs as BlockingDelayNs;
+use embedded_hal::digital::{Error as PinError, ErrorType, InputPin, OutputPin};
+use embedded_hal_async::delay::DelayNs;
+
+use embassy_futures::yield_now;
+
+use crate::error::OneWireFault;
+
+pub use nucleo_types::onewire::{crc8, known_family, PowerMode, Rom};
+
+/// Reads the ROM of the only slave on the bus
+pub const READ_ROM: u8 = 0x33;
+
+/// Selects the slave whose ROM follows
+pub const MATCH_ROM: u8 = 0x55;
+
+/// Selects all slaves on the bus
+pub const SKIP_ROM: u8 = 0xCC;
+
+/// Enumerates the ROMs of all slaves bit by bit
+pub const SEARCH_ROM: u8 = 0xF0;
+
+/// Enumerates the ROMs of the slaves with an alarm condition
+pub const ALARM_SEARCH: u8 = 0xEC;
+
+/// A parasite powered slave pulls the bus low in the read slot that follows
+pub const READ_POWER_SUPPLY: u8 = 0xB4;
+
+const RESET_LOW_US: u32 = 480;
+const PRESENCE_SAMPLE_US: u32 = 70;
+const PRESENCE_END_US: u32 = 410;
+const WRITE_ONE_LOW_US: u32 = 6;
+const WRITE_ONE_RECOVERY_US: u32 = 64;
+const WRITE_ZERO_LOW_US: u32 = 60;
+const WRITE_ZERO_RECOVERY_US: u32 = 10;
+const READ_LOW_US: u32 = 6;
+const READ_SAMPLE_US: u32 = 9;
+const READ_RECOVERY_US: u32 = 55;
+
+/// The state of an enumeration of the slaves on the bus, see [OneWire::search]
+///
+/// The slaves answer every bit of their ROM and its complement at once, the master follows one
+/// branch at a time and remembers the last position where the ROMs differ, such that the next
+/// search takes the other branch there.
+#[derive(Debug, Clone)]
+pub struct Search {
+    command: u8,
+    rom: Rom,
+    last_discrepancy: u8,
+    done: bool,
+}
+
+impl Search {
+    /// Enumerates all slaves with Search ROM
+    pub fn all() -> Self {
+        Self::with_command(SEARCH_ROM)
+    }
+
+    /// Enumerates the slaves with an alarm condition with Alarm Search
+    pub fn alarms() -> Self {
+        Self::with_command(ALARM_SEARCH)
+    }
+
+    fn with_command(command: u8) -> Self {
+        Search {
+            command,
+            rom: Rom::default(),
+            last_discrepancy: 0,
+            done: false,
+        }
+    }
+}
+
+/// A pin that can drive the bus high with a low impedance, e.g. by switching from open-drain to
+/// push-pull, to supply parasite powered slaves
+pub trait StrongPullUp: ErrorType {
+    /// Drives the bus high if enabled, leaves it to the pull-up resistor otherwise
+    fn set_strong_pull_up(&mut self, enable: bool) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OneWireError<E> {
+    /// no slave answered the reset with a presence pulse, or the slaves left during a search
+    NoPresence,
+
+    /// the CRC of a ROM or of data read from a slave does not match
+    Crc,
+
+    /// the pin could not be driven or read
+    Pin(E),
+}
+
+impl<E: PinError> fmt::Display for OneWireError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OneWireError::NoPresence => f.write_str("no 1-Wire device present"),
+            OneWireError::Crc => f.write_str("1-Wire CRC mismatch"),
+            OneWireError::Pin(error) => write!(f, "1-Wire pin failure: {:?}", error.kind()),
+        }
+    }
+}
+
+impl<E> OneWireError<E> {
+    /// Reduces the error to what is shown to the operator
+    pub fn fault(&self) -> OneWireFault {
+        match self {
+            OneWireError::NoPresence => OneWireFault::NoPresence,
+            OneWireError::Crc => OneWireFault::Crc,
+            OneWireError::Pin(_) => OneWireFault::Pin,
+        }
+    }
+}
+
+pub struct OneWire<P, D> {
+    pin: P,
+    delay: D,
+}
+
+impl<P: InputPin + OutputPin, D: DelayNs + BlockingDelayNs> OneWire<P, D> {
+    /// Creates a master on the given pin, the pin is expected to release the bus
+    pub fn new(pin: P, delay: D) -> Self {
+        OneWire { pin, delay }
+    }
+
+    /// Gives the pin and the delay back
+    pub fn release(self) -> (P, D) {
+        (self.pin, self.delay)
+    }
+
+    /// Resets all slaves, gives true if at least one answered with a presence pulse
+    pub async fn reset(&mut self) -> Result<bool, OneWireError<P::Error>> {
+        self.drive_low()?;
+        let driven = ReleaseOnDrop(&mut self.pin);
+        DelayNs::delay_us(&mut self.delay, RESET_LOW_US).await;
+        driven.disarm();
+        let presence = critical_section::with(|_| {
+            self.release_bus()?;
+            self.slot_delay(PRESENCE_SAMPLE_US);
+            self.pin.is_low().map_err(OneWireError::Pin)
+        })?;
+        self.wait(PRESENCE_END_US).await;
+        Ok(presence)
+    }
+
+    /// Writes a bit, the slot blocks, see the [module](self) documentation
+    pub fn write_bit(&mut self, bit: bool) -> Result<(), OneWireError<P::Error>> {
+        let (low, recovery) = if bit {
+            (WRITE_ONE_LOW_US, WRITE_ONE_RECOVERY_US)
+        } else {
+            (WRITE_ZERO_LOW_US, WRITE_ZERO_RECOVERY_US)
+        };
+        critical_section::with(|_| {
+            self.drive_low()?;
+            self.slot_delay(low);
+            self.release_bus()
+        })?;
+        // the slaves sample within the slot, a longer recovery does no harm
+        self.slot_delay(recovery);
+        Ok(())
+    }
+
+    /// Reads a bit, the slot blocks, see the [module](self) documentation
+    pub fn read_bit(&mut self) -> Result<bool, OneWireError<P::Error>> {
+        let bit = critical_section::with(|_| {
+            self.drive_low()?;
+            self.slot_delay(READ_LOW_US);
+            self.release_bus()?;
+            self.slot_delay(READ_SAMPLE_US);
+            self.pin.is_high().map_err(OneWireError::Pin)
+        })?;
+        self.slot_delay(READ_RECOVERY_US);
+        Ok(bit)
+    }
+
+    /// Writes the byte with its least significant bit first, then yields to the other tasks
+    pub async fn write_byte(&mut self, byte: u8) -> Result<(), OneWireError<P::Error>> {
+        for idx in 0..8 {
+            self.write_bit(byte & (1 << idx) != 0)?;
+        }
+        yield_now().await;
+        Ok(())
+    }
+
+    /// Reads a byte that is sent with its least significant bit first, then yields to the other
+    /// tasks
+    pub async fn read_byte(&mut self) -> Result<u8, OneWireError<P::Error>> {
+        let mut byte = 0;
+        for idx in 0..8 {
+            if self.read_bit()? {
+                byte |= 1 << idx;
+            }
+        }
+        yield_now().await;
+        Ok(byte)
+    }
+
+    pub async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), OneWireError<P::Error>> {
+        for byte in bytes {
+            self.write_byte(*byte).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), OneWireError<P::Error>> {
+        for byte in buf.iter_mut() {
+            *byte = self.read_byte().await?;
+        }
+        Ok(())
+    }
+
+    /// Reads the ROM of the only slave on the bus, it is selected afterwards
+    ///
+    /// With more than one slave the ROMs are ANDed on the bus and the CRC fails, use
+    /// [OneWire::search] instead.
+    pub async fn read_rom(&mut self) -> Result<Rom, OneWireError<P::Error>> {
+        self.rom_command(READ_ROM).await?;
+        let mut rom = Rom::default();
+        self.read_bytes(&mut rom.0).await?;
+        if !rom.is_valid() {
+            return Err(OneWireError::Crc);
+        }
+        Ok(rom)
+    }
+
+    /// Selects the slave with the given ROM, the others wait for the next reset
+    pub async fn match_rom(&mut self, rom: &Rom) -> Result<(), OneWireError<P::Error>> {
+        self.rom_command(MATCH_ROM).await?;
+        self.write_bytes(&rom.0).await
+    }
+
+    /// Selects all slaves, e.g. to start a conversion on all of them at once
+    pub async fn skip_rom(&mut self) -> Result<(), OneWireError<P::Error>> {
+        self.rom_command(SKIP_ROM).await
+    }
+
+    /// Gives the next ROM of the enumeration or None once all slaves have been found
+    ///
+    /// ```ignore
+    /// let mut search = Search::all();
+    /// while let Some(rom) = bus.search(&mut search).await? {
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// The found slave is selected afterwards. The enumeration ends on an error, as the bus has
+    /// changed during the search, it may be started again with a new [Search].
+    pub async fn search(&mut self, search: &mut Search) -> Result<Option<Rom>, OneWireError<P::Error>> {
+        if search.done {
+            return Ok(None);
+        }
+        search.done = true;
+        self.rom_command(search.command).await?;
+
+        let mut last_zero = 0;
+        for idx in 0..64 {
+            let bit = self.read_bit()?;
+            let complement = self.read_bit()?;
+            let direction = match (bit, complement) {
+                // no slave takes part, e.g. none has an alarm condition
+                (true, true) if idx == 0 => return Ok(None),
+                (true, true) => return Err(OneWireError::NoPresence),
+                (bit, complement) if bit != complement => bit,
+                _ => {
+                    // the ROMs differ, take the branch of the last search up to the last
+                    // discrepancy, the 1 branch there and the 0 branch afterwards
+                    let position = idx + 1;
+                    let direction = match position.cmp(&search.last_discrepancy) {
+                        core::cmp::Ordering::Less => search.rom.bit(idx),
+                        core::cmp::Ordering::Equal => true,
+                        core::cmp::Ordering::Greater => false,
+                    };
+                    if !direction {
+                        last_zero = position;
+                    }
+                    direction
+                }
+            };
+            search.rom.set_bit(idx, direction);
+            self.write_bit(direction)?;
+            if idx % 8 == 7 {
+                yield_now().await;
+            }
+        }
+
+        if !search.rom.is_valid() {
+            return Err(OneWireError::Crc);
+        }
+        search.last_discrepancy = last_zero;
+        search.done = last_zero == 0;
+        Ok(Some(search.rom))
+    }
+
+    /// Asks the selected slaves for their power supply, one parasite powered slave is enough to
+    /// give [PowerMode::Parasitic]
+    pub async fn read_power_supply(&mut self) -> Result<PowerMode, OneWireError<P::Error>> {
+        self.write_byte(READ_POWER_SUPPLY).await?;
+        match self.read_bit()? {
+            true => Ok(PowerMode::External),
+            false => Ok(PowerMode::Parasitic),
+        }
+    }
+
+    /// Tells how the slave with the given ROM is powered
+    pub async fn power_mode(&mut self, rom: &Rom) -> Result<PowerMode, OneWireError<P::Error>> {
+        self.match_rom(rom).await?;
+        self.read_power_supply().await
+    }
+
+    /// Resets the bus and sends the ROM command, fails if no slave is present
+    async fn rom_command(&mut self, command: u8) -> Result<(), OneWireError<P::Error>> {
+        if !self.reset().await? {
+            return Err(OneWireError::NoPresence);
+        }
+        self.write_byte(command).await
+    }
+
+    fn drive_low(&mut self) -> Result<(), OneWireError<P::Error>> {
+        self.pin.set_low().map_err(OneWireError::Pin)
+    }
+
+    fn release_bus(&mut self) -> Result<(), OneWireError<P::Error>> {
+        self.pin.set_high().map_err(OneWireError::Pin)
+    }
+
+    fn slot_delay(&mut self, us: u32) {
+        BlockingDelayNs::delay_us(&mut self.delay, us);
+    }
+
+    async fn wait(&mut self, us: u32) {
+        DelayNs::delay_us(&mut self.delay, us).await;
+    }
+}
+
+impl<P: InputPin + OutputPin + StrongPullUp, D: DelayNs + BlockingDelayNs> OneWire<P, D> {
+    /// Writes the function command and drives the bus high for the given time afterwards
+    ///
+    /// The strong pull-up ends the last write slot instead of the pull-up resistor, parasite
+    /// powered slaves need it within 10µs after a Convert T or Copy Scratchpad. The bus cannot be
+    /// used for anything else meanwhile.
+    pub async fn write_byte_powered(&mut self, byte: u8, powered_us: u32) -> Result<(), OneWireError<P::Error>> {
+        for idx in 0..7 {
+            self.write_bit(byte & (1 << idx) != 0)?;
+        }
+        let low = if byte & 0x80 != 0 {
+            WRITE_ONE_LOW_US
+        } else {
+            WRITE_ZERO_LOW_US
+        };
+        critical_section::with(|_| {
+            self.drive_low()?;
+            self.slot_delay(low);
+            self.pin.set_strong_pull_up(true).map_err(OneWireError::Pin)
+        })?;
+        let powered = EndPullUpOnDrop(&mut self.pin);
+        DelayNs::delay_us(&mut self.delay, powered_us).await;
+        powered.disarm();
+        self.pin.set_strong_pull_up(false).map_err(OneWireError::Pin)
+    }
+}
+
+/// Releases the bus if the transaction is dropped while the master drives it low
+struct ReleaseOnDrop<'a, P: OutputPin>(&'a mut P);
+
+impl<P: OutputPin> ReleaseOnDrop<'_, P> {
+    /// The transaction goes on and releases the bus itself
+    fn disarm(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl<P: OutputPin> Drop for ReleaseOnDrop<'_, P> {
+    fn drop(&mut self) {
+        // there is no one to report the error to
+        let _ = self.0.set_high();
+    }
+}
+
+/// Ends the strong pull-up if the transaction is dropped while it powers the slaves
+struct EndPullUpOnDrop<'a, P: StrongPullUp>(&'a mut P);
+
+impl<P: StrongPullUp> EndPullUpOnDrop<'_, P> {
+    /// The transaction goes on and ends the strong pull-up itself
+    fn disarm(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl<P: StrongPullUp> Drop for EndPullUpOnDrop<'_, P> {
+    fn drop(&mut self) {
+        let _ = self.0.set_strong_pull_up(false);
+    }
+}
+
+/// A simulated bus with slaves that check the timing of the master, it is shared with the tests
+/// of the drivers that use the bus
+#[cfg(test)]
+pub(crate) mod sim {
+    extern crate std;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    use core::convert::Infallible;
+
+    use embedded_hal::delay::DelayNs as BlockingDelayNs;
+    use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+    use embedded_hal_async::delay::DelayNs;
+
+    use super::{Rom, StrongPullUp, ALARM_SEARCH, MATCH_ROM, READ_POWER_SUPPLY, READ_ROM, SEARCH_ROM, SKIP_ROM};
+
+    /// The function command after which a selected slave sends its data
+    pub const READ_DATA: u8 = 0xBE;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum SlaveState {
+        RomCommand,
+        Matching(usize),
+        Searching(u8),
+        Selected,
+        Deselected,
+    }
+
+    pub struct Slave {
+        pub rom: Rom,
+
+        /// sent after [READ_DATA]
+        pub data: Vec<u8>,
+
+        /// the function bytes received while selected
+        pub received: Vec<u8>,
+
+        /// takes part in an Alarm Search
+        pub alarm: bool,
+
+        /// answers a Read Power Supply with a 0
+        pub parasitic: bool,
+
+        state: SlaveState,
+        byte: u8,
+        bits: u8,
+        transmit: VecDeque<bool>,
+        sending: bool,
+        low_from: u64,
+        low_until: u64,
+    }
+
+    impl Slave {
+        pub fn new(rom: [u8; 8], data: &[u8]) -> Self {
+            Slave {
+                rom: Rom(rom),
+                data: data.to_vec(),
+                received: Vec::new(),
+                alarm: false,
+                parasitic: false,
+                state: SlaveState::Deselected,
+                byte: 0,
+                bits: 0,
+                transmit: VecDeque::new(),
+                sending: false,
+                low_from: 0,
+                low_until: 0,
+            }
+        }
+
+        fn holds_low(&self, now: u64) -> bool {
+            (self.low_from..self.low_until).contains(&now)
+        }
+
+        fn queue(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.transmit.extend((0..8).map(|idx| byte & (1 << idx) != 0));
+            }
+        }
+
+        /// Sends the bit of the ROM and its complement
+        fn queue_search_bit(&mut self, idx: u8) {
+            let bit = self.rom.bit(idx);
+            self.transmit.extend([bit, !bit]);
+        }
+
+        fn reset(&mut self, now: u64) {
+            self.state = SlaveState::RomCommand;
+            self.byte = 0;
+            self.bits = 0;
+            self.transmit.clear();
+            self.sending = false;
+            // the presence pulse
+            self.low_from = now + 20;
+            self.low_until = now + 140;
+        }
+
+        fn slot_start(&mut self, now: u64) {
+            self.sending = false;
+            if let Some(bit) = self.transmit.pop_front() {
+                self.sending = true;
+                if !bit {
+                    self.low_from = now;
+                    self.low_until = now + 30;
+                }
+            }
+        }
+
+        fn slot_released(&mut self, low: u64) {
+            if self.sending {
+                assert!(low <= 15, "read slot held low for {}µs", low);
+                return;
+            }
+            let bit = match low {
+                1..=15 => true,
+                60..=120 => false,
+                _ => panic!("write slot held low for {}µs", low),
+            };
+            if let SlaveState::Searching(idx) = self.state {
+                // the master writes the direction, the slaves of the other branch drop out
+                self.state = if bit != self.rom.bit(idx) {
+                    SlaveState::Deselected
+                } else if idx == 63 {
+                    SlaveState::Selected
+                } else {
+                    self.queue_search_bit(idx + 1);
+                    SlaveState::Searching(idx + 1)
+                };
+                return;
+            }
+            self.byte |= (bit as u8) << self.bits;
+            self.bits += 1;
+            if self.bits == 8 {
+                let byte = self.byte;
+                self.byte = 0;
+                self.bits = 0;
+                self.on_byte(byte);
+            }
+        }
+
+        fn on_byte(&mut self, byte: u8) {
+            self.state = match self.state {
+                SlaveState::RomCommand => match byte {
+                    READ_ROM => {
+                        let rom = self.rom.0;
+                        self.queue(&rom);
+                        SlaveState::Selected
+                    }
+                    MATCH_ROM => SlaveState::Matching(0),
+                    SEARCH_ROM => {
+                        self.queue_search_bit(0);
+                        SlaveState::Searching(0)
+                    }
+                    ALARM_SEARCH if self.alarm => {
+                        self.queue_search_bit(0);
+                        SlaveState::Searching(0)
+                    }
+                    SKIP_ROM => SlaveState::Selected,
+                    _ => SlaveState::Deselected,
+                },
+                SlaveState::Matching(idx) if self.rom.0[idx] != byte => SlaveState::Deselected,
+                SlaveState::Matching(7) => SlaveState::Selected,
+                SlaveState::Matching(idx) => SlaveState::Matching(idx + 1),
+                SlaveState::Selected => {
+                    self.received.push(byte);
+                    if byte == READ_DATA {
+                        let data = self.data.clone();
+                        self.queue(&data);
+                    }
+                    if byte == READ_POWER_SUPPLY && self.parasitic {
+                        self.transmit.push_back(false);
+                    }
+                    SlaveState::Selected
+                }
+                SlaveState::Searching(_) | SlaveState::Deselected => SlaveState::Deselected,
+            };
+        }
+    }
+
+    /// The time in microseconds and the state of the bus
+    #[derive(Default)]
+    pub struct Bus {
+        now: u64,
+        master_low_since: Option<u64>,
+        slot_start: Option<u64>,
+        strong_since: Option<u64>,
+        pub slaves: Vec<Slave>,
+
+        /// how long the master drove the bus high each time, see [StrongPullUp]
+        pub strong_pull_ups: Vec<u64>,
+
+        /// the line is shorted to ground, e.g. by a damaged cable
+        pub shorted: bool,
+
+        /// the async waits pass their time but never end while the master drives the bus low or
+        /// high, such that a test may drop the transaction there
+        pub hang_while_driven: bool,
+    }
+
+    impl Bus {
+        pub fn new(slaves: Vec<Slave>) -> Rc<RefCell<Bus>> {
+            Rc::new(RefCell::new(Bus {
+                slaves,
+                ..Default::default()
+            }))
+        }
+
+        fn line_low(&self) -> bool {
+            self.shorted || self.master_low_since.is_some() || self.slaves.iter().any(|slave| slave.holds_low(self.now))
+        }
+
+        fn drive_low(&mut self) {
+            if self.master_low_since.is_some() {
+                return;
+            }
+            let now = self.now;
+            assert!(self.strong_since.is_none(), "slot started during the strong pull-up at {}µs", now);
+            assert!(
+                !self.slaves.iter().any(|slave| slave.holds_low(now)),
+                "slot started while a slave holds the bus at {}µs",
+                now
+            );
+            if let Some(start) = self.slot_start {
+                assert!(now - start >= 61, "slot of {}µs is too short", now - start);
+            }
+            self.slot_start = Some(now);
+            self.master_low_since = Some(now);
+            for slave in self.slaves.iter_mut() {
+                slave.slot_start(now);
+            }
+        }
+
+        fn release(&mut self) {
+            let Some(since) = self.master_low_since.take() else {
+                return;
+            };
+            let (now, low) = (self.now, self.now - since);
+            if low >= 480 {
+                self.slot_start = None;
+                for slave in self.slaves.iter_mut() {
+                    slave.reset(now);
+                }
+            } else {
+                for slave in self.slaves.iter_mut() {
+                    slave.slot_released(low);
+                }
+            }
+        }
+
+        fn sample(&self) -> bool {
+            if self.slaves.iter().any(|slave| slave.sending) {
+                let start = self.slot_start.unwrap();
+                assert!(self.now - start <= 15, "read slot sampled after {}µs", self.now - start);
+            }
+            self.line_low()
+        }
+    }
+
+    pub struct Pin(pub Rc<RefCell<Bus>>);
+
+    impl StrongPullUp for Pin {
+        fn set_strong_pull_up(&mut self, enable: bool) -> Result<(), Self::Error> {
+            let mut bus = self.0.borrow_mut();
+            if enable {
+                bus.release();
+                bus.strong_since = Some(bus.now);
+            } else if let Some(since) = bus.strong_since.take() {
+                let powered = bus.now - since;
+                bus.strong_pull_ups.push(powered);
+            }
+            Ok(())
+        }
+    }
+
+    impl ErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().drive_low();
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().release();
+            Ok(())
+        }
+    }
+
+    impl InputPin for Pin {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.0.borrow().sample())
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.0.borrow().sample())
+        }
+    }
+
+    /// Advances the time of the bus, nanoseconds are rounded up to microseconds
+    pub struct Delay(pub Rc<RefCell<Bus>>);
+
+    impl DelayNs for Delay {
+        async fn delay_ns(&mut self, ns: u32) {
+            BlockingDelayNs::delay_ns(self, ns);
+            let hang = {
+                let bus = self.0.borrow();
+                bus.hang_while_driven && (bus.master_low_since.is_some() || bus.strong_since.is_some())
+            };
+            if hang {
+                core::future::pending::<()>().await;
+            }
+        }
+    }
+
+    impl BlockingDelayNs for Delay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.borrow_mut().now += ns.div_ceil(1000) as u64;
+        }
+    }
+
+    /// A master on a bus with the given slaves
+    pub fn master(slaves: Vec<Slave>) -> (super::OneWire<Pin, Delay>, Rc<RefCell<Bus>>) {
+        let bus = Bus::new(slaves);
+        (super::OneWire::new(Pin(bus.clone()), Delay(bus.clone())), bus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::sim::{master, Pin, Slave, READ_DATA};
+    use super::*;
+    use embassy_futures::{block_on, poll_once};
+
+    const ROM_A: [u8; 8] = [0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x34];
+    const ROM_B: [u8; 8] = [0x28, 0x3D, 0x12, 0x6B, 0x0B, 0x00, 0x00, 0x83];
+    // differs from A in the last bit of the serial only
+    const ROM_C: [u8; 8] = [0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x80, 0xB8];
+    const ROM_D: [u8; 8] = [0x10, 0x3D, 0x12, 0x6B, 0x0B, 0x08, 0x00, 0x10];
+
+    fn search_all(bus: &mut OneWire<super::sim::Pin, super::sim::Delay>, mut search: Search) -> Vec<Rom> {
+        let mut roms = Vec::new();
+        while let Some(rom) = block_on(bus.search(&mut search)).unwrap() {
+            roms.push(rom);
+        }
+        roms.sort();
+        roms
+    }
+
+    #[test]
+    fn reset_detects_presence() {
+        let (mut bus, _) = master(vec![Slave::new(ROM_A, &[])]);
+        assert_eq!(block_on(bus.reset()), Ok(true));
+        assert_eq!(block_on(bus.reset()), Ok(true));
+
+        let (mut bus, _) = master(Vec::new());
+        assert_eq!(block_on(bus.reset()), Ok(false));
+        assert_eq!(block_on(bus.skip_rom()), Err(OneWireError::NoPresence));
+        assert_eq!(block_on(bus.read_rom()), Err(OneWireError::NoPresence));
+    }
+
+    #[test]
+    fn writes_and_reads_bytes() {
+        let data = [0x00, 0xFF, 0xA5, 0x5A, 0x01, 0x80];
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &data)]);
+
+        block_on(bus.skip_rom()).unwrap();
+        block_on(bus.write_bytes(&[0x44, 0x00, 0xFF])).unwrap();
+        block_on(bus.skip_rom()).unwrap();
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        let mut buf = [0u8; 6];
+        block_on(bus.read_bytes(&mut buf)).unwrap();
+
+        assert_eq!(buf, data);
+        assert_eq!(sim.borrow().slaves[0].received, [0x44, 0x00, 0xFF, READ_DATA]);
+    }
+
+    #[test]
+    fn released_bus_reads_ones() {
+        let (mut bus, _) = master(vec![Slave::new(ROM_A, &[])]);
+        block_on(bus.skip_rom()).unwrap();
+        assert_eq!(bus.read_bit(), Ok(true));
+        assert_eq!(block_on(bus.read_byte()), Ok(0xFF));
+    }
+
+    #[test]
+    fn read_rom_of_the_only_slave() {
+        let (mut bus, _) = master(vec![Slave::new(ROM_A, &[0x42])]);
+
+        let rom = block_on(bus.read_rom()).unwrap();
+        assert_eq!(rom, Rom(ROM_A));
+        assert_eq!(rom.family_code(), 0x28);
+        assert_eq!(rom.serial(), 0x0000_0F1E_64FF);
+        assert_eq!(rom.crc(), 0x34);
+        assert_eq!(std::format!("{}", rom), "28-00000f1e64ff");
+
+        // the slave is selected after Read ROM
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        assert_eq!(block_on(bus.read_byte()), Ok(0x42));
+    }
+
+    #[test]
+    fn shorted_bus_gives_no_rom() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[])]);
+        sim.borrow_mut().shorted = true;
+
+        // the reset mistakes the short for a presence pulse and every bit reads 0
+        assert_eq!(block_on(bus.reset()), Ok(true));
+        assert_eq!(block_on(bus.read_rom()), Err(OneWireError::Crc));
+        assert_eq!(block_on(bus.search(&mut Search::all())), Err(OneWireError::Crc));
+    }
+
+    #[test]
+    fn match_rom_selects_one_slave() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[0x0A]), Slave::new(ROM_B, &[0x0B])]);
+
+        block_on(bus.match_rom(&Rom(ROM_B))).unwrap();
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        assert_eq!(block_on(bus.read_byte()), Ok(0x0B));
+
+        block_on(bus.match_rom(&Rom(ROM_A))).unwrap();
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        assert_eq!(block_on(bus.read_byte()), Ok(0x0A));
+
+        let sim = sim.borrow();
+        assert_eq!(sim.slaves[0].received, [READ_DATA]);
+        assert_eq!(sim.slaves[1].received, [READ_DATA]);
+    }
+
+    #[test]
+    fn skip_rom_selects_all_slaves() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[0xF0]), Slave::new(ROM_B, &[0x3C])]);
+
+        block_on(bus.skip_rom()).unwrap();
+        block_on(bus.write_byte(0x44)).unwrap();
+        assert_eq!(sim.borrow().slaves[0].received, [0x44]);
+        assert_eq!(sim.borrow().slaves[1].received, [0x44]);
+
+        // both answer at once, the bus gives the AND of their data
+        block_on(bus.skip_rom()).unwrap();
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        assert_eq!(block_on(bus.read_byte()), Ok(0x30));
+    }
+
+    #[test]
+    fn search_finds_every_slave() {
+        let roms = [ROM_A, ROM_B, ROM_C, ROM_D];
+        let (mut bus, _) = master(roms.iter().map(|rom| Slave::new(*rom, &[])).collect());
+
+        let mut expected: Vec<Rom> = roms.iter().map(|rom| Rom(*rom)).collect();
+        expected.sort();
+        assert_eq!(search_all(&mut bus, Search::all()), expected);
+
+        let (mut bus, _) = master(vec![Slave::new(ROM_B, &[])]);
+        assert_eq!(search_all(&mut bus, Search::all()), [Rom(ROM_B)]);
+    }
+
+    #[test]
+    fn search_selects_the_found_slave() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[0x0A]), Slave::new(ROM_C, &[0x0C])]);
+        let mut search = Search::all();
+
+        let first = block_on(bus.search(&mut search)).unwrap().unwrap();
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        let data = block_on(bus.read_byte()).unwrap();
+        assert_eq!(data, if first == Rom(ROM_A) { 0x0A } else { 0x0C });
+        let received: Vec<usize> = sim.borrow().slaves.iter().map(|slave| slave.received.len()).collect();
+        assert_eq!(received.iter().sum::<usize>(), 1);
+    }
+
+    #[test]
+    fn alarm_search_finds_alarmed_slaves() {
+        let mut slaves: Vec<Slave> = [ROM_A, ROM_B, ROM_C, ROM_D].iter().map(|rom| Slave::new(*rom, &[])).collect();
+        let (mut bus, _) = master(Vec::new());
+        assert_eq!(block_on(bus.search(&mut Search::alarms())), Err(OneWireError::NoPresence));
+
+        slaves[1].alarm = true;
+        slaves[2].alarm = true;
+        let (mut bus, sim) = master(slaves);
+        assert_eq!(search_all(&mut bus, Search::alarms()), [Rom(ROM_B), Rom(ROM_C)]);
+
+        for slave in sim.borrow_mut().slaves.iter_mut() {
+            slave.alarm = false;
+        }
+        let mut search = Search::alarms();
+        assert_eq!(block_on(bus.search(&mut search)), Ok(None));
+        assert_eq!(block_on(bus.search(&mut search)), Ok(None));
+    }
+
+    #[test]
+    fn reads_the_power_supply() {
+        let mut slaves = vec![Slave::new(ROM_A, &[]), Slave::new(ROM_B, &[])];
+        slaves[1].parasitic = true;
+        let (mut bus, _) = master(slaves);
+        assert_eq!(block_on(bus.power_mode(&Rom(ROM_A))), Ok(PowerMode::External));
+        assert_eq!(block_on(bus.power_mode(&Rom(ROM_B))), Ok(PowerMode::Parasitic));
+
+        // a single parasite powered slave pulls the bus low for all
+        block_on(bus.skip_rom()).unwrap();
+        assert_eq!(block_on(bus.read_power_supply()), Ok(PowerMode::Parasitic));
+    }
+
+    #[test]
+    fn strong_pull_up_follows_the_command() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[])]);
+        block_on(bus.skip_rom()).unwrap();
+        block_on(bus.write_byte_powered(0x44, 750_000)).unwrap();
+        // the last bit is a 1 here
+        block_on(bus.write_byte_powered(0xC8, 10_000)).unwrap();
+        assert_eq!(sim.borrow().strong_pull_ups, [750_000, 10_000]);
+        assert_eq!(sim.borrow().slaves[0].received, [0x44, 0xC8]);
+        assert_eq!(block_on(bus.reset()), Ok(true));
+    }
+
+    #[test]
+    fn dropped_transactions_release_the_bus() {
+        let (mut bus, sim) = master(vec![Slave::new(ROM_A, &[0x42])]);
+        sim.borrow_mut().hang_while_driven = true;
+
+        assert!(poll_once(bus.reset()).is_pending());
+        assert_eq!(Pin(sim.clone()).is_high(), Ok(true));
+
+        sim.borrow_mut().hang_while_driven = false;
+        block_on(bus.skip_rom()).unwrap();
+        sim.borrow_mut().hang_while_driven = true;
+        assert!(poll_once(bus.write_byte_powered(0x44, 750_000)).is_pending());
+        assert_eq!(sim.borrow().strong_pull_ups, [750_000]);
+
+        // a slot during the strong pull-up or a missed reset would fail here
+        sim.borrow_mut().hang_while_driven = false;
+        block_on(bus.skip_rom()).unwrap();
+        block_on(bus.write_byte(READ_DATA)).unwrap();
+        assert_eq!(block_on(bus.read_byte()), Ok(0x42));
+    }
+
+    #[test]
+    fn corrupted_roms_are_rejected() {
+        let corrupted = [0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x35];
+        let (mut bus, _) = master(vec![Slave::new(corrupted, &[])]);
+        assert_eq!(block_on(bus.read_rom()), Err(OneWireError::Crc));
+        let mut search = Search::all();
+        assert_eq!(block_on(bus.search(&mut search)), Err(OneWireError::Crc));
+        assert_eq!(block_on(bus.search(&mut search)), Ok(None));
+
+        // Read ROM of two slaves gives the AND of their ROMs
+        let (mut bus, _) = master(vec![Slave::new(ROM_A, &[]), Slave::new(ROM_B, &[])]);
+        assert_eq!(block_on(bus.read_rom()), Err(OneWireError::Crc));
+    }
+}
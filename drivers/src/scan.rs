@@ -0,0 +1,134 @@
+//! Probing an I2C bus for the devices that are connected to it, see [scan].
+//!
+//! Every address of [SCAN_RANGE] is probed with a read of one byte, a device that acknowledges
+//! its address is present. A zero-length transfer would touch the devices less, but it is not
+//! supported by every HAL, e.g. the DMA based I2C of embassy-stm32 rejects it. The addresses
+//! outside of the range are reserved by the I2C specification.
+//!
+//! Addresses of devices that are supported by a driver of this crate are named by
+//! [known_device], such that a scan tells which part is on the bus.
+
+use embedded_hal_async::i2c::{Error as I2cError, ErrorKind, I2c};
+
+use crate::error::{i2c_fault, BusFault};
+
+pub use nucleo_types::scan::{known_device, SCAN_RANGE};
+
+/// The set of addresses that acknowledged a probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Devices(u128);
+
+impl Devices {
+    pub fn insert(&mut self, addr: u8) {
+        self.0 |= 1 << (addr & 0x7F);
+    }
+
+    pub fn contains(&self, addr: u8) -> bool {
+        addr < 0x80 && self.0 & (1 << addr) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Gives the addresses in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..0x80).filter(|addr| self.contains(*addr))
+    }
+
+    /// The 16 addresses starting at `row * 0x10` as bits, the lowest bit is the first address
+    pub fn row(&self, row: u8) -> u16 {
+        (self.0 >> ((row as u32 & 0x7) * 16)) as u16
+    }
+}
+
+/// The scan has been stopped as the bus failed with another error than a NACK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanError<E> {
+    pub addr: u8,
+    pub error: E,
+}
+
+impl<E: I2cError> ScanError<E> {
+    /// Gives the kind of the error and the address that was probed
+    pub fn fault(&self) -> BusFault {
+        i2c_fault(&self.error, self.addr)
+    }
+}
+
+/// Probes every address of [SCAN_RANGE] and gives the ones that acknowledged
+///
+/// A NACK means that there is no device at the address, any other error stops the scan as the
+/// bus itself is in trouble, e.g. a device holds SDA low.
+pub async fn scan<I2C: I2c>(mut i2c: I2C) -> Result<Devices, ScanError<I2C::Error>> {
+    let mut devices = Devices::default();
+    let mut buf = [0u8; 1];
+    for addr in SCAN_RANGE {
+        match i2c.read(addr, &mut buf).await {
+            Ok(()) => devices.insert(addr),
+            Err(error) if matches!(error.kind(), ErrorKind::NoAcknowledge(_)) => {}
+            Err(error) => return Err(ScanError { addr, error }),
+        }
+    }
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::bh1750::{BH1750_ADDR_H, BH1750_ADDR_L};
+    use crate::error::BusFaultKind;
+    use embassy_futures::block_on;
+    use embedded_hal_async::i2c::NoAcknowledgeSource;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    const NACK: ErrorKind = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+
+    fn probe(addr: u8, present: &[u8]) -> Transaction {
+        let transaction = Transaction::read(addr, vec![0]);
+        if present.contains(&addr) {
+            transaction
+        } else {
+            transaction.with_error(NACK)
+        }
+    }
+
+    #[test]
+    fn finds_acknowledging_devices() {
+        let present = [0x08, BH1750_ADDR_L, BH1750_ADDR_H, 0x77];
+        let expectations: Vec<_> = SCAN_RANGE.map(|addr| probe(addr, &present)).collect();
+        let mut i2c = Mock::new(&expectations);
+
+        let devices = block_on(scan(&mut i2c)).unwrap();
+        assert_eq!(devices.iter().collect::<Vec<_>>(), present);
+        assert_eq!(devices.row(0), 1 << 8);
+        assert_eq!(devices.row(2), 1 << 3);
+        assert_eq!(devices.row(5), 1 << 12);
+        assert_eq!(devices.row(7), 1 << 7);
+        assert_eq!(devices.row(1), 0);
+        i2c.done();
+    }
+
+    #[test]
+    fn stops_on_bus_errors() {
+        let expectations = [
+            probe(0x08, &[]),
+            Transaction::read(0x09, vec![0]).with_error(ErrorKind::ArbitrationLoss),
+        ];
+        let mut i2c = Mock::new(&expectations);
+
+        let err = block_on(scan(&mut i2c)).unwrap_err();
+        assert_eq!(err.fault(), BusFault { kind: BusFaultKind::ArbitrationLoss, addr: 0x09 });
+        i2c.done();
+    }
+
+    #[test]
+    fn names_the_parts_of_the_drivers() {
+        assert_eq!(known_device(BH1750_ADDR_L), Some("BH1750"));
+        assert_eq!(known_device(BH1750_ADDR_H), Some("BH1750"));
+    }
+}
@@ -1,147 +1,279 @@
-//! Functions to communicate with the BH1750FVI digital 16bit light sensor
+//! Glue between the firmware and the BH1750FVI digital 16bit light sensor
+//!
+//! The board independent driver is [Bh1750] of the `nucleo_drivers` crate. The functions of this
+//! module operate it through an [I2cDevice] of the shared bus and keep the shared state of a [SharedLightSensor]
+//! up to date. The firmware keeps one [SharedLightSensor] per address, such that a BH1750FVI with
+//! its ADDR pin low and one with it high are operated on the same bus.
+//!
+//! Errors are returned as [LightSensorError], [light_sensor_fault] reduces them to the [BusFault]
+//! that is shown in the status report, e.g. `sensor error: NACK @0x23`.
+//!
+//! A [Bh1750Sensor] operates one of them as a [Sensor] of the registry.
 
-use embassy_stm32::mode::Async;
 
 use embassy_sync::mutex::Mutex;
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::channel::{Channel, Receiver, Sender};
 use embassy_sync::signal::Signal;
 
-use embassy_stm32::i2c::I2c;
+use embassy_stm32::i2c::Error as I2cError;
 
-use cortex_m_semihosting::hprintln;
+use embassy_time::{Delay, Duration};
 
-use crate::cmd::LightSensorCommands;
+use crate::cmd::{LightReport, LightResolution, LightSensorCommands, StatusReport};
+use crate::i2c::{bus_fault, I2cAsyncMutex, I2cDevice};
+use crate::sensor::{Reading, Sensor, SensorFault, SensorId, SensorSignal, SensorState};
 
-pub const BH1750_ADDR_H: u8 = 0x5C;
-pub const BH1750_ADDR_L: u8 = 0x23;
-
-// the following constants are private and their funcitonality is implemented in this module
-const BH1750_OPC_POWDOWN: u8    = 0b0000;
-const BH1750_OPC_POWUP: u8      = 0b0001;
-const BH1750_OPC_CO_MES_HR1: u8 = 0b01_0000;
-const BH1750_OPC_OT_MES_HR1: u8 = 0b10_0000;
-
-#[allow(dead_code)]
-const BH1750_OPC_RESET: u8      = 0b0111;
-#[allow(dead_code)]
-const BH1750_OPC_CO_MES_HR2: u8 = 0b01_0001;
-#[allow(dead_code)]
-const BH1750_OPC_CO_MES_LR1: u8 = 0b01_0011;
-#[allow(dead_code)]
-const BH1750_OPC_OT_MES_HR2: u8 = 0b10_0001;
-#[allow(dead_code)]
-const BH1750_OPC_OT_MES_LR1: u8 = 0b10_0011;
-// todo change measurement time
-// Write Format BH1750FVI is not able to accept plural command without stop condition. Please insert SP every 1 Opecode.
+pub use nucleo_cmd::cmd::LightSensorState;
+pub use nucleo_drivers::error::BusFault;
+pub use nucleo_drivers::units::Lux;
+pub use nucleo_drivers::bh1750::{Bh1750, Bh1750Error, Config as Bh1750Config, Resolution, BH1750_ADDR_H, BH1750_ADDR_L, MTREG_DEFAULT};
 
 pub type LightCommandChannel<const N: usize> = Channel<ThreadModeRawMutex, LightSensorCommands, N>;
 pub type LightCommandSender<const N: usize> = Sender<'static, ThreadModeRawMutex, LightSensorCommands, N>;
 pub type LightCommandReceiver<const N: usize> = Receiver<'static, ThreadModeRawMutex, LightSensorCommands, N>;
 
-pub type LightSensorValueType = u16;
+/// The result of the last measurement, an error is kept such that it is shown in the status report
+pub type LightSensorValueType = Result<Lux, BusFault>;
 pub type SyncedLightSensorValueType = Mutex<ThreadModeRawMutex, Option<LightSensorValueType>>;
 
 pub type LightSensorStateSync = Mutex<ThreadModeRawMutex, LightSensorState>;
+pub type LightSensorConfigSync = Mutex<ThreadModeRawMutex, Bh1750Config>;
 pub type LightSensorCollectSignal = Signal<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, ()>;
 
-#[derive(Clone, Copy)]
-pub enum LightSensorState {
-    PowerOff,
-    SingleMeasurement,
-    ContiniousMeasurement,
-}
-
-impl LightSensorState {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            LightSensorState::PowerOff => "off",
-            LightSensorState::SingleMeasurement => "single",
-            LightSensorState::ContiniousMeasurement => "on",
+/// The errors of the driver on the I2C bus of the board
+pub type LightSensorError = Bh1750Error<I2cError>;
+
+/// The state, configuration and last value of one BH1750FVI that are shared between the tasks
+pub struct SharedLightSensor {
+    /// the address on the bus, [BH1750_ADDR_L] or [BH1750_ADDR_H]
+    pub addr: u8,
+    pub state: LightSensorStateSync,
+    pub config: LightSensorConfigSync,
+    pub value: SyncedLightSensorValueType,
+
+    /// interrupts the continuous measurement of this sensor on a change of its state
+    pub signal: LightSensorCollectSignal,
+}
+
+impl SharedLightSensor {
+    /// A powered off sensor at the given address with the default configuration
+    pub const fn new(addr: u8) -> Self {
+        SharedLightSensor {
+            addr,
+            state: LightSensorStateSync::new(LightSensorState::PowerOff),
+            config: LightSensorConfigSync::new(Bh1750Config {
+                resolution: Resolution::High,
+                mtreg: MTREG_DEFAULT,
+            }),
+            value: SyncedLightSensorValueType::new(None),
+            signal: LightSensorCollectSignal::new(),
         }
     }
-}
 
-pub async fn single_measurement(
-    addr: u8, 
-    i2c: &mut I2c<'static, Async>, 
-    shared_state: &'static LightSensorStateSync
-) -> u16 {
-    let mut buf_opcode: [u8; 1] = [0; 1];
+    /// Keeps the result of a measurement for queries and the status report, bus errors are kept too
+    pub async fn store(&self, res: &Result<Lux, LightSensorError>) {
+        let value = match res {
+            Ok(lux) => Ok(*lux),
+            Err(err) => match light_sensor_fault(err) {
+                Some(fault) => Err(fault),
+                None => return,
+            },
+        };
+        let mut unlocked = self.value.lock().await;
+        *unlocked = Some(value);
+    }
+}
 
-    {
-        let mut unlocked = shared_state.lock().await;
-        *unlocked = LightSensorState::SingleMeasurement;
+/// Gives the kind and address of a bus error, unlike [Bh1750Error::fault] it recognizes timeouts
+pub fn light_sensor_fault(err: &LightSensorError) -> Option<BusFault> {
+    match err {
+        Bh1750Error::I2c { addr, error } => Some(bus_fault(*addr, error)),
+        err => err.fault(),
     }
+}
 
-    buf_opcode[0] = BH1750_OPC_POWUP;
-    let res = i2c.write(addr, &buf_opcode).await;
-    if let Err(err) = res {
-        hprintln!("Write Error: {:?} at addr={}", err, addr);
-        return 0;
+/// Maps the resolution of the console to the one of the driver
+pub fn resolution_from_cmd(resolution: LightResolution) -> Resolution {
+    match resolution {
+        LightResolution::High => Resolution::High,
+        LightResolution::High2 => Resolution::High2,
+        LightResolution::Low => Resolution::Low,
     }
-    buf_opcode[0] = BH1750_OPC_OT_MES_HR1;
-    let res = i2c.write(addr, &buf_opcode).await;
-    if let Err(err) = res {
-        hprintln!("Write Error: {:?} at addr={}", err, addr);
-        return 0;
+}
+
+/// Maps the resolution of the driver to the one of the console
+pub fn resolution_to_cmd(resolution: Resolution) -> LightResolution {
+    match resolution {
+        Resolution::High => LightResolution::High,
+        Resolution::High2 => LightResolution::High2,
+        Resolution::Low => LightResolution::Low,
     }
+}
 
-    let mut rx_buf: [u8; 2] = [0; 2];
-    hprintln!("READ I2C 2 bytes");
-    let res = i2c.read(addr, &mut rx_buf).await;
-    if let Err(err) = res {
-        hprintln!("Read Error: {:?} at addr={}", err, addr);
-        return 0;
+pub async fn single_measurement(
+    sensor: &SharedLightSensor,
+    i2c: &mut I2cDevice<'_>,
+) -> Result<Lux, LightSensorError> {
+    let config = { *sensor.config.lock().await };
+    {
+        let mut unlocked = sensor.state.lock().await;
+        *unlocked = LightSensorState::SingleMeasurement;
     }
 
+    let res = Bh1750::with_config(i2c, sensor.addr, config).one_shot(&mut Delay).await;
+
     {
-        let mut unlocked = shared_state.lock().await;
+        let mut unlocked = sensor.state.lock().await;
         *unlocked = LightSensorState::PowerOff;
     }
 
-    hprintln!("Bytes {}_{}", rx_buf[0], rx_buf[1]);
-    ((rx_buf[0] as u16) << 8) | rx_buf[1] as u16
+    res.map(|raw| config.lux(raw))
 }
 
 pub async fn continious_measurement(
-    addr: u8,
-    i2c: &mut I2c<'static, Async>,
-    shared_state: &'static LightSensorStateSync
-) {
+    sensor: &SharedLightSensor,
+    i2c: &mut I2cDevice<'_>,
+) -> Result<(), LightSensorError> {
+    let config = { *sensor.config.lock().await };
     {
-        let mut unlocked = shared_state.lock().await;
+        let mut unlocked = sensor.state.lock().await;
         *unlocked = LightSensorState::ContiniousMeasurement;
     }
 
-    let mut buf_opcode: [u8; 1] = [0; 1];
-    buf_opcode[0] = BH1750_OPC_POWUP;
-    let res = i2c.write(addr, &buf_opcode).await;
-    if let Err(err) = res {
-        hprintln!("Write Error: {:?} at addr={}", err, addr);
-        return;
-    }
-    buf_opcode[0] = BH1750_OPC_CO_MES_HR1;
-    let res = i2c.write(addr, &buf_opcode).await;
-    if let Err(err) = res {
-        hprintln!("Write Error: {:?} at addr={}", err, addr);
-    }
+    Bh1750::with_config(i2c, sensor.addr, config).start_continuous(&mut Delay).await
+}
+
+/// The time between two results of a continuous measurement with the current config
+pub async fn conversion_time(sensor: &SharedLightSensor) -> Duration {
+    let config = { *sensor.config.lock().await };
+    Duration::from_micros(config.conversion_time_us() as u64)
+}
+
+/// Reads the latest result of a continuous measurement
+pub async fn read_measurement(
+    sensor: &SharedLightSensor,
+    i2c: &mut I2cDevice<'_>,
+) -> Result<Lux, LightSensorError> {
+    let config = { *sensor.config.lock().await };
+    let raw = Bh1750::with_config(i2c, sensor.addr, config).read().await?;
+    Ok(config.lux(raw))
 }
 
 pub async fn power_off(
-    addr: u8,
-    i2c: &mut I2c<'static, Async>,
-    shared_state: &'static LightSensorStateSync
-) {
+    sensor: &SharedLightSensor,
+    i2c: &mut I2cDevice<'_>,
+) -> Result<(), LightSensorError> {
     {
-        let mut unlocked = shared_state.lock().await;
+        let mut unlocked = sensor.state.lock().await;
         *unlocked = LightSensorState::PowerOff;
     }
 
-    let mut buf_opcode: [u8; 1] = [0; 1];
-    buf_opcode[0] = BH1750_OPC_POWDOWN;
-    let res = i2c.write(addr, &buf_opcode).await;
-    if let Err(err) = res {
-        hprintln!("Write Error: {:?} at addr={}", err, addr);
+    Bh1750::new(i2c, sensor.addr).power_down().await
+}
+
+/// Selects the resolution of the following measurements, a running continuous measurement is restarted
+pub async fn set_resolution(
+    sensor: &SharedLightSensor,
+    i2c: &mut I2cDevice<'_>,
+    resolution: Resolution,
+) -> Result<(), LightSensorError> {
+    let mut config = sensor.config.lock().await;
+    config.resolution = resolution;
+    if *sensor.state.lock().await == LightSensorState::ContiniousMeasurement {
+        Bh1750::with_config(i2c, sensor.addr, *config).start_continuous(&mut Delay).await?;
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+/// Writes the measurement time register of the sensor
+pub async fn set_measurement_time(
+    sensor: &SharedLightSensor,
+    i2c: &mut I2cDevice<'_>,
+    mtreg: u8,
+) -> Result<(), LightSensorError> {
+    let mut config = sensor.config.lock().await;
+    let mut driver = Bh1750::with_config(i2c, sensor.addr, *config);
+    driver.set_mtreg(mtreg).await?;
+    *config = driver.config();
+    Ok(())
+}
+
+impl From<LightSensorError> for SensorFault {
+    fn from(err: LightSensorError) -> Self {
+        match light_sensor_fault(&err) {
+            Some(fault) => SensorFault::I2c(fault),
+            None => SensorFault::Config,
+        }
+    }
+}
+
+/// A light sensor with the bus it is connected to, see [Sensor]
+#[derive(Clone, Copy)]
+pub struct Bh1750Sensor {
+    id: u8,
+    shared: &'static SharedLightSensor,
+    bus: &'static I2cAsyncMutex,
+}
+
+impl Bh1750Sensor {
+    /// The light sensor with the given id, it is addressed as given by its shared state
+    pub fn new(id: u8, shared: &'static SharedLightSensor, bus: &'static I2cAsyncMutex) -> Self {
+        Bh1750Sensor { id, shared, bus }
+    }
+
+    pub fn shared(&self) -> &'static SharedLightSensor {
+        self.shared
+    }
+}
+
+impl Sensor for Bh1750Sensor {
+    fn id(&self) -> SensorId {
+        SensorId::Light(self.id)
+    }
+
+    fn unit(&self) -> &'static str {
+        "Lux"
+    }
+
+    async fn state(&self) -> SensorState {
+        let state = { *self.shared.state.lock().await };
+        state.into()
+    }
+
+    async fn start(&self) -> Result<(), SensorFault> {
+        Ok(continious_measurement(self.shared, &mut I2cDevice::new(self.bus)).await?)
+    }
+
+    async fn stop(&self) -> Result<(), SensorFault> {
+        Ok(power_off(self.shared, &mut I2cDevice::new(self.bus)).await?)
+    }
+
+    async fn sample(&self) -> Result<Reading, SensorFault> {
+        let mut device = I2cDevice::new(self.bus);
+        let res = match self.state().await {
+            SensorState::Continuous => read_measurement(self.shared, &mut device).await,
+            SensorState::Off | SensorState::Single => single_measurement(self.shared, &mut device).await,
+        };
+        self.shared.store(&res).await;
+        Ok(Reading::Light(self.id, res?))
+    }
+
+    async fn sample_interval(&self) -> Duration {
+        conversion_time(self.shared).await
+    }
+
+    fn signal(&self) -> &SensorSignal {
+        &self.shared.signal
+    }
+
+    async fn report(&self, report: &mut StatusReport) {
+        let Some(light) = (self.id as usize).checked_sub(1).and_then(|idx| report.light.get_mut(idx)) else {
+            return;
+        };
+        *light = LightReport {
+            state: *self.shared.state.lock().await,
+            value: *self.shared.value.lock().await,
+        };
+    }
+}
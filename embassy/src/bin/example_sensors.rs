@@ -1,14 +1,13 @@
 #![no_std]
 #![no_main]
 
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use core::fmt::Write;
 use heapless::String;
 
 use static_cell::{StaticCell};
 
 use embassy_sync::channel::Channel;
-use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex;
 
 use embassy_futures::select::select;
@@ -17,16 +16,15 @@ use embassy_futures::select::Either;
 use embassy_executor::Spawner;
 use embassy_stm32::mode::Async;
 use embassy_stm32::bind_interrupts;
-use embassy_stm32::i2c::I2c;
 use embassy_stm32::{
     exti::ExtiInput,
     gpio::{AnyPin, Level, Output, Pin, Pull, Speed},
     usart::{Uart, UartRx, UartTx},
 };
+use embassy_stm32::{peripherals, rcc};
 use embassy_stm32::usart::Config as UsartConfig;
-use embassy_stm32::i2c::Config as I2cConfig;
 
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 use {defmt_rtt as _, panic_probe as _};
 use cortex_m_semihosting::hprintln;
 
@@ -37,27 +35,39 @@ use nucleo_f767zi::led::LedSignal;
 
 use nucleo_f767zi::cmd::str_to_command;
 use nucleo_f767zi::cmd::{CommandChannel, CommandSender, CommandReceiver};
+use nucleo_f767zi::cmd::{Response, ResponseChannel, ResponseSender, ResponseReceiver};
+use nucleo_f767zi::cmd::{ConsoleMode, Reply, Request};
 use nucleo_f767zi::cmd::Commands::*;
 use nucleo_f767zi::cmd::Commands;
-use nucleo_f767zi::cmd::LightSensorCommands;
+use nucleo_f767zi::cmd::{I2cBytes, I2cCommands, LightSensorCommands, OneWireCommands, TemperatureSensorCommands, I2C_MAX_LEN};
+use nucleo_f767zi::cmd::COMMANDS;
+use nucleo_f767zi::cmd::Query;
+use nucleo_f767zi::cmd::{LightReport, StatusReport, TemperatureReport, LIGHT_SENSOR_COUNT};
+use nucleo_f767zi::cmd::SensorId;
 
 use nucleo_f767zi::bh1750fvi::LightSensorState;
-use nucleo_f767zi::bh1750fvi::LightSensorStateSync;
-use nucleo_f767zi::bh1750fvi::SyncedLightSensorValueType;
-use nucleo_f767zi::bh1750fvi::LightSensorCollectSignal;
+use nucleo_f767zi::bh1750fvi::{Bh1750Sensor, SharedLightSensor};
+use nucleo_f767zi::bh1750fvi::{resolution_from_cmd, resolution_to_cmd, set_resolution, set_measurement_time};
 
-use nucleo_f767zi::bh1750fvi::{single_measurement, continious_measurement, power_off};
-use nucleo_f767zi::bh1750fvi::BH1750_ADDR_L;
+use nucleo_f767zi::bh1750fvi::{BH1750_ADDR_H, BH1750_ADDR_L};
+use nucleo_f767zi::bh1750fvi::{light_sensor_fault, BusFault, LightSensorError};
 
-use nucleo_f767zi::setup_usart_developer_console;
-use nucleo_f767zi::uart::parse_uart_tx_as_utf8;
+use nucleo_f767zi::ds18b20::{self, Ds18b20Resolution, Ds18b20Sensor, SharedTemperatureSensor, TemperatureSensorState};
+
+use nucleo_f767zi::sensor::{AnySensor, Sensor, SensorFault, SensorRegistry, SensorState};
+use nucleo_f767zi::scheduler::run_scheduler;
 
-use embassy_stm32::time::Hertz;
+use nucleo_f767zi::i2c::{scan_bus, transfer, I2cAsyncMutex, I2cDevice, I2cParts, RecoveringI2c, BUS_RECOVERIES};
+use nucleo_f767zi::onewire::{self, new_bus, OneWireAsyncMutex, OneWireBusError, OneWireDelay, Search};
+use nucleo_f767zi::setup_usart_developer_console;
+use nucleo_f767zi::uart::{write_reply, ConsoleReader, UartTxMutex};
 
-type I2cAsyncMutex = mutex::Mutex<CriticalSectionRawMutex, I2c<'static, Async>>;
 
 static STATUS_INTERVAL_MS: AtomicU32 = AtomicU32::new(10000);
 
+/// true if the developer console speaks binary frames instead of text
+static BINARY_MODE: AtomicBool = AtomicBool::new(false);
+
 static LED_STATE_RED: LedStateSync = LedStateSync::new(LedState::Manual(false));
 static LED_STATE_GREEN: LedStateSync = LedStateSync::new(LedState::Manual(false));
 static LED_STATE_BLUE: LedStateSync = LedStateSync::new(LedState::Manual(false));
@@ -66,11 +76,21 @@ static SIGNAL_RED: LedSignal = LedSignal::new();
 static SIGNAL_GREEN: LedSignal = LedSignal::new();
 static SIGNAL_BLUE: LedSignal = LedSignal::new();
 
-static LIGHT_SENSOR_STATE: LightSensorStateSync = LightSensorStateSync::new(LightSensorState::PowerOff);
-static LIGHT_SENSOR_VALUE: SyncedLightSensorValueType = SyncedLightSensorValueType::new(None);
-static LIGHT_SENSOR_SIGNAL: LightSensorCollectSignal = LightSensorCollectSignal::new();
+/// the light sensors by id, 1 is indoor with its ADDR pin low and 2 at the window with ADDR high
+static LIGHT_SENSORS: [SharedLightSensor; LIGHT_SENSOR_COUNT] = [
+    SharedLightSensor::new(BH1750_ADDR_L),
+    SharedLightSensor::new(BH1750_ADDR_H),
+];
+
+/// the DS18B20 as the only device on the 1-Wire bus
+static TEMPERATURE_SENSOR: SharedTemperatureSensor = SharedTemperatureSensor::new();
+
+/// the sampling periods in continuous measurement until they are changed with `sample`
+const LIGHT_SAMPLE_PERIOD_MS: u32 = 150;
+const TEMPERATURE_SAMPLE_PERIOD_MS: u32 = 1000;
 
 static CHANNEL_COMMANDS: CommandChannel = Channel::new();
+static CHANNEL_RESPONSES: ResponseChannel = Channel::new();
 
 
 #[embassy_executor::task(pool_size=3)]
@@ -82,117 +102,446 @@ async fn led_wrapper(pin: AnyPin, synced_state: &'static LedStateSync, signal: &
 #[embassy_executor::task]
 async fn command_executor(
     command_receiver: CommandReceiver,
-    signal_red: &'static LedSignal,
-    signal_green: &'static LedSignal,
-    signal_blue: &'static LedSignal,
-    signal_light: &'static LightSensorCollectSignal,
+    response_sender: ResponseSender,
     i2c: &'static I2cAsyncMutex,
+    onewire: &'static OneWireAsyncMutex,
+    sensors: &'static SensorRegistry,
 ) {
     loop {
-        let cmd = command_receiver.receive().await;
-        match cmd {
+        let request = command_receiver.receive().await;
+        let (seq, cmd) = (request.seq, request.payload);
+        let response = match cmd {
             UartStatusReport(ms) => {
                 hprintln!("Changed UART reporting to {}ms", ms);
                 STATUS_INTERVAL_MS.store(ms, Ordering::Relaxed);
+                Response::Ok(cmd)
             }
             Led(id, new_state) => {
-                {
-                    let (mut unlocked, sig) = match id {
-                        1 => (LED_STATE_RED.lock().await, signal_red),
-                        2 => (LED_STATE_GREEN.lock().await, signal_green),
-                        3 => (LED_STATE_BLUE.lock().await, signal_blue),
-                        _ => { return; }
-                    };
-
-                    *unlocked = new_state;
-                    drop(unlocked);
+                if let (Some(synced_state), Some(sig)) = (led_state_sync(id), led_signal(id)) {
+                    {
+                        let mut unlocked = synced_state.lock().await;
+                        *unlocked = new_state;
+                    }
                     sig.signal(());
+                    Response::Ok(cmd)
+                } else {
+                    Response::error(&"unknown led id")
                 }
             }
-            LightSensor(sub_cmd) => {
+            LightSensor(id, sub_cmd) => {
+                let (Some(sensor), Some(shared)) = (sensors.get(SensorId::Light(id)), light_sensor(id)) else {
+                    response_sender.send(Reply::new(seq, Response::error(&"unknown light sensor id"))).await;
+                    continue;
+                };
+                let mut device = I2cDevice::new(i2c);
                 match sub_cmd {
-                    LightSensorCommands::Off => {
-                        signal_light.signal(());
-                        power_off(BH1750_ADDR_L, &mut *(i2c.lock().await), &LIGHT_SENSOR_STATE).await;
+                    LightSensorCommands::Off => operate(seq, cmd, sensor, SensorState::Off, &response_sender).await,
+                    LightSensorCommands::SingleMeasurment => operate(seq, cmd, sensor, SensorState::Single, &response_sender).await,
+                    LightSensorCommands::ContiniousMeasurement => operate(seq, cmd, sensor, SensorState::Continuous, &response_sender).await,
+                    LightSensorCommands::Resolution(resolution) => {
+                        hprintln!("Light resolution {} at sensor {}", resolution, id);
+                        let resolution = resolution_from_cmd(resolution);
+                        light_sensor_response(cmd, set_resolution(shared, &mut device, resolution).await)
+                    }
+                    LightSensorCommands::MeasurementTime(mtreg) => {
+                        hprintln!("Light MTreg {} at sensor {}", mtreg, id);
+                        light_sensor_response(cmd, set_measurement_time(shared, &mut device, mtreg).await)
                     }
-                    LightSensorCommands::SingleMeasurment => {
-                        signal_light.signal(());
-                        let lux = single_measurement(BH1750_ADDR_L, &mut *(i2c.lock().await), &LIGHT_SENSOR_STATE).await;
-                        hprintln!("{} Lux light intensity", lux);
-
-                        {
-                            let mut unlock = LIGHT_SENSOR_VALUE.lock().await;
-                            *unlock = Some(lux);
+                }
+            }
+            TemperatureSensor(sub_cmd) => {
+                let (Some(any), Some(sensor)) = (sensors.get(SensorId::Temperature), temperature_sensor(sensors)) else {
+                    response_sender.send(Reply::new(seq, Response::error(&"no temperature sensor"))).await;
+                    continue;
+                };
+                match sub_cmd {
+                    TemperatureSensorCommands::Off => operate(seq, cmd, any, SensorState::Off, &response_sender).await,
+                    TemperatureSensorCommands::SingleMeasurement => operate(seq, cmd, any, SensorState::Single, &response_sender).await,
+                    TemperatureSensorCommands::ContinuousMeasurement => operate(seq, cmd, any, SensorState::Continuous, &response_sender).await,
+                    TemperatureSensorCommands::Resolution(bits) => match Ds18b20Resolution::from_bits(bits) {
+                        Some(resolution) => {
+                            hprintln!("Temperature resolution {} bits", bits);
+                            let res = ds18b20::set_resolution(sensor, onewire, resolution).await;
+                            // the next conversion is awaited in the new resolution
+                            any.signal().signal(());
+                            temperature_sensor_response(cmd, res)
                         }
+                        None => Response::error(&"resolution has to be 9..12 bits"),
+                    },
+                    TemperatureSensorCommands::Alarm(lo, hi) => {
+                        hprintln!("Temperature alarm limits {} and {} °C", lo, hi);
+                        temperature_sensor_response(cmd, ds18b20::set_alarm(sensor, onewire, lo, hi).await)
                     }
-                    LightSensorCommands::ContiniousMeasurement => {
-                        hprintln!("Light Continous");
-                        signal_light.signal(());
-                        continious_measurement(BH1750_ADDR_L, &mut *(i2c.lock().await), &LIGHT_SENSOR_STATE).await;
-
+                }
+            }
+            Sample(id, ms) => match sensors.scheduled(id) {
+                Some(scheduled) => {
+                    hprintln!("Sampling {} every {}ms", id, ms);
+                    scheduled.set_period(ms);
+                    Response::Ok(cmd)
+                }
+                None => Response::error(&"unknown sensor"),
+            },
+            Get(query) => {
+                if send_query_values(seq, query, sensors, &response_sender).await {
+                    Response::Ok(cmd)
+                } else {
+                    Response::error(&"unknown led or sensor id")
+                }
+            }
+            State => {
+                for query in Query::ALL {
+                    send_query_values(seq, query, sensors, &response_sender).await;
+                }
+                Response::Ok(cmd)
+            }
+            Mode(mode) => {
+                // the receiver has switched the mode already, such that it reads the next bytes correctly
+                hprintln!("Console switched to {} mode", mode);
+                Response::Ok(cmd)
+            }
+            I2cBus(op) => {
+                let res = match op {
+                    I2cCommands::Scan => scan(seq, i2c, &response_sender).await,
+                    I2cCommands::Write(addr, bytes) => raw_transfer(seq, i2c, addr, bytes.as_slice(), 0, &response_sender).await,
+                    I2cCommands::Read(addr, len) => raw_transfer(seq, i2c, addr, &[], len, &response_sender).await,
+                    I2cCommands::WriteRead(addr, bytes, len) => raw_transfer(seq, i2c, addr, bytes.as_slice(), len, &response_sender).await,
+                };
+                match res {
+                    Ok(()) => Response::Ok(cmd),
+                    Err(fault) => {
+                        hprintln!("I2C {} failed: {}", op, fault);
+                        Response::error(&format_args!("bus error: {}", fault))
+                    }
+                }
+            }
+            OneWireBus(op) => {
+                let res = match op {
+                    OneWireCommands::Scan => onewire_scan(seq, onewire, Search::all(), &response_sender).await,
+                    OneWireCommands::AlarmSearch => onewire_scan(seq, onewire, Search::alarms(), &response_sender).await,
+                };
+                match res {
+                    Ok(()) => Response::Ok(cmd),
+                    Err(err) => {
+                        hprintln!("1-Wire {} failed: {}", op, err);
+                        Response::error(&err)
+                    }
+                }
+            }
+            Help(topic) => {
+                match topic {
+                    None => {
+                        for idx in 0..COMMANDS.len() {
+                            response_sender.send(Reply::new(seq, Response::Usage(idx as u8))).await;
+                        }
+                    }
+                    Some(idx) => {
+                        response_sender.send(Reply::new(seq, Response::Usage(idx))).await;
+                        let num_args = COMMANDS.get(idx as usize).map(|spec| spec.args.len()).unwrap_or(0);
+                        for arg_idx in 0..num_args {
+                            response_sender.send(Reply::new(seq, Response::ArgUsage(idx, arg_idx as u8))).await;
+                        }
                     }
                 }
+                Response::Ok(cmd)
             }
-            _ => {}
+        };
+        response_sender.send(Reply::new(seq, response)).await;
+    }
+}
+
+fn led_state_sync(id: u8) -> Option<&'static LedStateSync> {
+    match id {
+        1 => Some(&LED_STATE_RED),
+        2 => Some(&LED_STATE_GREEN),
+        3 => Some(&LED_STATE_BLUE),
+        _ => None,
+    }
+}
+
+fn led_signal(id: u8) -> Option<&'static LedSignal> {
+    match id {
+        1 => Some(&SIGNAL_RED),
+        2 => Some(&SIGNAL_GREEN),
+        3 => Some(&SIGNAL_BLUE),
+        _ => None,
+    }
+}
+
+/// Answers the devices on the bus as a grid like the one of `i2cdetect`
+async fn scan(seq: u16, i2c: &'static I2cAsyncMutex, response_sender: &ResponseSender) -> Result<(), BusFault> {
+    let devices = scan_bus(i2c).await?;
+    response_sender.send(Reply::new(seq, Response::ScanHeader)).await;
+    for row in 0..8 {
+        response_sender.send(Reply::new(seq, Response::ScanRow(row, devices.row(row)))).await;
+    }
+    for addr in devices.iter() {
+        response_sender.send(Reply::new(seq, Response::Device(addr))).await;
+    }
+    Ok(())
+}
+
+/// Answers the ROM of every device on the 1-Wire bus that the search finds
+async fn onewire_scan(
+    seq: u16,
+    bus: &'static OneWireAsyncMutex,
+    search: Search,
+    response_sender: &ResponseSender,
+) -> Result<(), OneWireBusError> {
+    for (rom, power) in onewire::scan_bus(bus, search).await? {
+        response_sender.send(Reply::new(seq, Response::OneWireDevice(rom, power))).await;
+    }
+    Ok(())
+}
+
+/// Writes the bytes and reads `len` bytes from the device, the read bytes are answered as value
+async fn raw_transfer(
+    seq: u16,
+    i2c: &'static I2cAsyncMutex,
+    addr: u8,
+    write: &[u8],
+    len: u8,
+    response_sender: &ResponseSender,
+) -> Result<(), BusFault> {
+    let mut buf = [0u8; I2C_MAX_LEN];
+    let read = &mut buf[..(len as usize).min(I2C_MAX_LEN)];
+    transfer(i2c, addr, write, read).await?;
+    if let Some(data) = I2cBytes::new(read).filter(|_| len > 0) {
+        response_sender.send(Reply::new(seq, Response::I2cData(addr, data))).await;
+    }
+    Ok(())
+}
+
+fn light_sensor(id: u8) -> Option<&'static SharedLightSensor> {
+    (id as usize).checked_sub(1).and_then(|idx| LIGHT_SENSORS.get(idx))
+}
+
+/// The shared state of the registered temperature sensor
+fn temperature_sensor(sensors: &SensorRegistry) -> Option<&'static SharedTemperatureSensor> {
+    match sensors.get(SensorId::Temperature)? {
+        AnySensor::Ds18b20(sensor) => Some(sensor.shared()),
+        _ => None,
+    }
+}
+
+fn light_sensor_response(cmd: Commands, res: Result<(), LightSensorError>) -> Response {
+    let Err(err) = res else {
+        return Response::Ok(cmd);
+    };
+    hprintln!("Light sensor error: {}", err);
+    match light_sensor_fault(&err) {
+        Some(fault) => Response::error(&format_args!("sensor error: {}", fault)),
+        None => Response::error(&err),
+    }
+}
+
+fn temperature_sensor_response(cmd: Commands, res: Result<(), OneWireBusError>) -> Response {
+    let Err(err) = res else {
+        return Response::Ok(cmd);
+    };
+    hprintln!("Temperature sensor error: {}", err);
+    Response::error(&format_args!("sensor error: {}", err.fault()))
+}
+
+/// Switches a sensor of the registry to the given state, a single measurement answers its reading
+async fn operate(seq: u16, cmd: Commands, sensor: &AnySensor, state: SensorState, response_sender: &ResponseSender) -> Response {
+    let res = switch_sensor(seq, sensor, state, response_sender).await;
+    // the continuous measurement follows the new state
+    sensor.signal().signal(());
+    match res {
+        Ok(()) => Response::Ok(cmd),
+        Err(fault) => {
+            hprintln!("Sensor error at {}: {}", sensor.id(), fault);
+            Response::error(&format_args!("sensor error: {}", fault))
+        }
+    }
+}
+
+async fn switch_sensor(seq: u16, sensor: &AnySensor, state: SensorState, response_sender: &ResponseSender) -> Result<(), SensorFault> {
+    match state {
+        SensorState::Off => sensor.stop().await,
+        SensorState::Continuous => {
+            hprintln!("Continuous measurement at {}", sensor.id());
+            sensor.start().await
+        }
+        SensorState::Single => {
+            // a single measurement ends a continuous one
+            sensor.stop().await?;
+            let reading = sensor.sample().await?;
+            hprintln!("{} {} at {}", reading, sensor.unit(), sensor.id());
+            response_sender.send(Reply::new(seq, reading.into())).await;
+            Ok(())
         }
     }
 }
 
+/// Sends the values asked for by the query in the syntax of the commands that set them,
+/// returns false if the query addresses an unknown LED or sensor
+async fn send_query_values(seq: u16, query: Query, sensors: &SensorRegistry, response_sender: &ResponseSender) -> bool {
+    match query {
+        Query::Led(id) => {
+            let Some(synced_state) = led_state_sync(id) else {
+                return false;
+            };
+            let state = { *synced_state.lock().await };
+            response_sender.send(Reply::new(seq, Response::State(Led(id, state)))).await;
+        }
+        Query::Status => {
+            let ms = STATUS_INTERVAL_MS.load(Ordering::Relaxed);
+            response_sender.send(Reply::new(seq, Response::State(UartStatusReport(ms)))).await;
+        }
+        Query::Light(id) => {
+            let Some(sensor) = light_sensor(id) else {
+                return false;
+            };
+            let state = { *sensor.state.lock().await };
+            response_sender.send(Reply::new(seq, Response::State(LightSensor(id, state.into())))).await;
+            let config = { *sensor.config.lock().await };
+            let resolution = LightSensorCommands::Resolution(resolution_to_cmd(config.resolution));
+            response_sender.send(Reply::new(seq, Response::State(LightSensor(id, resolution)))).await;
+            let mtreg = LightSensorCommands::MeasurementTime(config.mtreg);
+            response_sender.send(Reply::new(seq, Response::State(LightSensor(id, mtreg)))).await;
+            let value = { *sensor.value.lock().await };
+            if let Some(Ok(value)) = value {
+                response_sender.send(Reply::new(seq, Response::LightValue(id, value))).await;
+            }
+        }
+        Query::Temperature => {
+            let Some(sensor) = temperature_sensor(sensors) else {
+                return false;
+            };
+            let state = { *sensor.state.lock().await };
+            response_sender.send(Reply::new(seq, Response::State(TemperatureSensor(state.into())))).await;
+            let config = { *sensor.config.lock().await };
+            let resolution = TemperatureSensorCommands::Resolution(config.resolution.bits());
+            response_sender.send(Reply::new(seq, Response::State(TemperatureSensor(resolution)))).await;
+            let alarm = TemperatureSensorCommands::Alarm(config.tl, config.th);
+            response_sender.send(Reply::new(seq, Response::State(TemperatureSensor(alarm)))).await;
+            let value = { *sensor.value.lock().await };
+            if let Some(Ok(value)) = value {
+                response_sender.send(Reply::new(seq, Response::TemperatureValue(value))).await;
+            }
+        }
+        Query::Sample(id) => {
+            let Some(scheduled) = sensors.scheduled(id) else {
+                return false;
+            };
+            response_sender.send(Reply::new(seq, Response::State(Sample(id, scheduled.period_ms())))).await;
+        }
+    }
+    true
+}
+
+/// Switches the console mode as soon as a `mode` command is received
+fn switch_console_mode(cmd: &Commands) {
+    if let Mode(mode) = cmd {
+        BINARY_MODE.store(*mode == ConsoleMode::Binary, Ordering::Relaxed);
+    }
+}
+
 #[embassy_executor::task]
-async fn uart_receiver_and_cmd_forwarder(mut usart_rx: UartRx<'static, Async>, command_sender: CommandSender) {
-    let mut buf: [u8; 48] = [0; 48];
+async fn uart_receiver_and_cmd_forwarder(
+    mut usart_rx: UartRx<'static, Async>,
+    usart_tx: &'static UartTxMutex,
+    command_sender: CommandSender,
+    response_sender: ResponseSender,
+) {
+    let mut reader: ConsoleReader<64, 8> = ConsoleReader::new();
     loop {
-        let msg = match parse_uart_tx_as_utf8(&mut usart_rx, &mut buf).await {
+        if BINARY_MODE.load(Ordering::Relaxed) {
+            match reader.read_frame(&mut usart_rx).await {
+                Ok(request) => {
+                    switch_console_mode(&request.payload);
+                    command_sender.send(request).await;
+                }
+                Err(reason) => {
+                    hprintln!("Frame rejected: {}", reason);
+                    response_sender.send(Reply::new(0, Response::error(&reason))).await;
+                }
+            }
+            continue;
+        }
+
+        let msg = match reader.read_line(&mut usart_rx, usart_tx).await {
             Ok(msg) => msg,
-            Err(_) => continue,
+            Err(reason) => {
+                response_sender.send(Reply::new(0, Response::error(&reason))).await;
+                continue;
+            }
         };
 
-        if let Some(cmd) = str_to_command(msg) {
-            hprintln!("UART sends command");
-            command_sender.send(cmd).await;
+        // an empty line is no command and needs no answer
+        if msg.is_empty() {
+            continue;
         }
 
-        // Add a small delay to yield control back to the executor
-        Timer::after(Duration::from_millis(10)).await;
+        match str_to_command(&msg) {
+            Ok(cmd) => {
+                hprintln!("UART sends command");
+                switch_console_mode(&cmd);
+                command_sender.send(Request::new(0, cmd)).await;
+            }
+            Err(err) => {
+                hprintln!("Command rejected: {}", err);
+                response_sender.send(Reply::new(0, Response::error(&err))).await;
+            }
+        }
     }
 }
 
+/// Writes responses of the command executor as soon as they arrive and a status report
+/// every [STATUS_INTERVAL_MS], the status report is suppressed in binary mode
 #[embassy_executor::task]
-async fn uart_status_report_transmitter(mut usart_tx: UartTx<'static, Async>) {
+async fn uart_transmitter(usart_tx: &'static UartTxMutex, response_receiver: ResponseReceiver, sensors: &'static SensorRegistry) {
+    let mut next_report = Instant::now();
     loop {
         let interval: u64 = STATUS_INTERVAL_MS.load(Ordering::Relaxed).into();
-        if interval == 0 {
-            Timer::after(Duration::from_millis(250)).await;
+        let deadline = if interval == 0 {
+            Instant::now() + Duration::from_millis(250)
         } else {
-            hprintln!("UART Report!");
-
-            let mut msg: String<256> = String::new();
-            msg.push_str("Status: Light Sensor ").unwrap();
-            {
-                let unlocked = LIGHT_SENSOR_STATE.lock().await;
-                let temp: &str = (*unlocked).as_str();
-                msg.push_str(temp).unwrap();
-                
-            }
+            next_report
+        };
 
-            {
-                let unlocked = LIGHT_SENSOR_VALUE.lock().await;
-                let mut buf: String<16> = String::new();
-                if let Some(value) = *unlocked {
-                    core::write!(&mut buf, " - {} Lux", value).unwrap();
-                    msg.push_str(buf.as_str()).unwrap();
-                } else {
-                    msg.push_str(" - No sensor value yet").unwrap();
+        match select(Timer::at(deadline), response_receiver.receive()).await {
+            Either::First(_) => {
+                if interval != 0 && !BINARY_MODE.load(Ordering::Relaxed) {
+                    uart_status_report(&mut *(usart_tx.lock().await), sensors).await;
+                    next_report = Instant::now() + Duration::from_millis(interval);
                 }
             }
-
-            msg.push_str("\r\n").unwrap();
-            usart_tx.write(&msg.into_bytes()).await.unwrap();
-            Timer::after(Duration::from_millis(interval)).await;
+            Either::Second(reply) => {
+                let mode = if BINARY_MODE.load(Ordering::Relaxed) {
+                    ConsoleMode::Binary
+                } else {
+                    ConsoleMode::Text
+                };
+                write_reply(usart_tx, &reply, mode).await.unwrap();
+            }
         }
     }
 }
 
+async fn uart_status_report(usart_tx: &mut UartTx<'static, Async>, sensors: &SensorRegistry) {
+    hprintln!("UART Report!");
+
+    let mut report = StatusReport {
+        light: [LightReport { state: LightSensorState::PowerOff, value: None }; LIGHT_SENSOR_COUNT],
+        temperature: TemperatureReport { state: TemperatureSensorState::PowerOff, value: None },
+        bus_recoveries: BUS_RECOVERIES.load(Ordering::Relaxed),
+    };
+    for sensor in sensors.iter() {
+        sensor.report(&mut report).await;
+    }
+
+    let mut msg: String<256> = String::new();
+    core::write!(&mut msg, "{}\r\n", report).unwrap();
+    usart_tx.write(&msg.into_bytes()).await.unwrap();
+}
+
+bind_interrupts!(struct Irqs {
+    USART3 => embassy_stm32::usart::InterruptHandler<embassy_stm32::peripherals::USART3>;
+});
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_stm32::init(Default::default());
@@ -212,26 +561,37 @@ async fn main(spawner: Spawner) {
         &LED_STATE_BLUE, 
         &SIGNAL_BLUE)).unwrap();
 
-    // bind interrupts
-    bind_interrupts!(struct Irqs {
-        USART3 => embassy_stm32::usart::InterruptHandler<embassy_stm32::peripherals::USART3>;
-        I2C1_EV => embassy_stm32::i2c::EventInterruptHandler<embassy_stm32::peripherals::I2C1>;
-        I2C1_ER => embassy_stm32::i2c::ErrorInterruptHandler<embassy_stm32::peripherals::I2C1>;
-    });
-    
     // start i2c for sensor:
-    let i2c: I2c<'static, Async> = I2c::new(
-        p.I2C1,
-        p.PB8, // scl
-        p.PB9, // sda
-        Irqs,
-        p.DMA1_CH6, // dma tx
-        p.DMA1_CH0, // dma rx
-        Hertz(100_000), // SCL clock frequency !?
-        I2cConfig::default(),
-    );
+    let parts = I2cParts {
+        i2c: p.I2C1,
+        scl: p.PB8,
+        sda: p.PB9,
+        tx_dma: p.DMA1_CH6,
+        rx_dma: p.DMA1_CH0,
+    };
     static I2C: StaticCell<I2cAsyncMutex> = StaticCell::new();
-    let i2c = I2C.init(mutex::Mutex::new(i2c));
+    let i2c = I2C.init(mutex::Mutex::new(RecoveringI2c::new(parts)));
+
+    // start 1-Wire bus on A0 of the Arduino header
+    static ONEWIRE: StaticCell<OneWireAsyncMutex> = StaticCell::new();
+    // the core runs from HCLK, the clock of the AHB peripherals such as the DMA
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    let sysclk = rcc::frequency::<peripherals::DMA1>();
+    let delay = OneWireDelay::new(&mut core.DCB, &mut core.DWT, sysclk);
+    let onewire = ONEWIRE.init(mutex::Mutex::new(new_bus(p.PA3, delay)));
+
+    // the sensors are operated through the registry by the tasks below
+    static SENSORS: StaticCell<SensorRegistry> = StaticCell::new();
+    let sensors = SENSORS.init(SensorRegistry::new());
+    for (id, shared) in (1..).zip(LIGHT_SENSORS.iter()) {
+        if sensors.register(Bh1750Sensor::new(id, shared, i2c), LIGHT_SAMPLE_PERIOD_MS).is_err() {
+            hprintln!("Light sensor {} is not registered", id);
+        }
+    }
+    if sensors.register(Ds18b20Sensor::new(&TEMPERATURE_SENSOR, onewire), TEMPERATURE_SAMPLE_PERIOD_MS).is_err() {
+        hprintln!("Temperature sensor is not registered");
+    }
+    let sensors: &'static SensorRegistry = sensors;
 
     // start developer usart 
     let mut usart = setup_usart_developer_console!(p, Irqs, UsartConfig::default());
@@ -239,61 +599,30 @@ async fn main(spawner: Spawner) {
     
     // spawn a task for uart sending and receiving each
     let (tx, rx) = usart.split();
-    spawner.spawn(uart_receiver_and_cmd_forwarder(rx, CHANNEL_COMMANDS.sender())).unwrap();
-    spawner.spawn(uart_status_report_transmitter(tx)).unwrap();
+    static USART_TX: StaticCell<UartTxMutex> = StaticCell::new();
+    let tx = USART_TX.init(mutex::Mutex::new(tx));
+    spawner.spawn(uart_receiver_and_cmd_forwarder(rx, tx, CHANNEL_COMMANDS.sender(), CHANNEL_RESPONSES.sender())).unwrap();
+    spawner.spawn(uart_transmitter(tx, CHANNEL_RESPONSES.receiver(), sensors)).unwrap();
     
-    spawner.spawn(process_light_sensor(
-        &LIGHT_SENSOR_SIGNAL,
-        i2c
-    )).unwrap();
+    spawner.spawn(sensor_scheduler(sensors)).unwrap();
 
     // spawn the main logic driven by a channel of commands
     spawner.spawn(command_executor(
         CHANNEL_COMMANDS.receiver(), 
-        &SIGNAL_RED, 
-        &SIGNAL_GREEN, 
-        &SIGNAL_BLUE,
-        &LIGHT_SENSOR_SIGNAL,
-        i2c)).unwrap();
+        CHANNEL_RESPONSES.sender(),
+        i2c,
+        onewire,
+        sensors)).unwrap();
 
     loop {
         button.wait_for_rising_edge().await;
-        CHANNEL_COMMANDS.sender().send(Commands::LightSensor(LightSensorCommands::SingleMeasurment)).await;
+        CHANNEL_COMMANDS.sender().send(Request::new(0, Commands::LightSensor(1, LightSensorCommands::SingleMeasurment))).await;
         Timer::after(Duration::from_millis(50)).await;
     }
 }
 
+/// Takes the readings of all sensors in continuous measurement at their sampling periods
 #[embassy_executor::task]
-async fn process_light_sensor(signal: &'static LightSensorCollectSignal, i2c: &'static I2cAsyncMutex) {
-    loop {
-        let state = {
-            *(LIGHT_SENSOR_STATE.lock().await)
-        };
-
-        match state {
-            LightSensorState::ContiniousMeasurement => {
-                let mut rx_buf: [u8; 2] = [0; 2];
-                let res = {
-                    let i2c = &mut (*i2c.lock().await);
-                    let f1 = i2c.read(BH1750_ADDR_L, &mut rx_buf);
-                    let f2 = signal.wait();
-                    select(f1, f2).await
-                };
-
-                if let Either::First(res) = res {
-                    if let Err(err) = res {
-                        hprintln!("Write Error: {:?} at addr={}", err, BH1750_ADDR_L);
-                    } else {
-                        let mut unlocked = LIGHT_SENSOR_VALUE.lock().await;
-                        *unlocked = Some(((rx_buf[0] as u16) << 8) | rx_buf[1] as u16);
-                    }
-                } else {
-                    hprintln!("Continious i2c reading interrupted by signal");
-                }
-                
-                Timer::after(Duration::from_millis(150)).await;
-            }
-            LightSensorState::PowerOff | LightSensorState::SingleMeasurement => signal.wait().await,
-        }
-    }
-}
\ No newline at end of file
+async fn sensor_scheduler(sensors: &'static SensorRegistry) {
+    run_scheduler(sensors).await;
+}
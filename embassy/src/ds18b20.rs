@@ -0,0 +1,248 @@
+//! Glue between the firmware and the DS18B20 temperature sensor on the 1-Wire bus
+//!
+//! The board independent driver is [Ds18b20] of the `nucleo_drivers` crate. The sensor is the only
+//! slave on the bus and is addressed with Skip ROM. The functions of this module keep the shared
+//! state of a [SharedTemperatureSensor] up to date.
+//!
+//! A measurement locks the bus for the Convert T and again for the Read Scratchpad only, in between
+//! the conversion time of the configured resolution is awaited with a [Timer], such that a
+//! `onewire scan` is not blocked for up to 750ms. A parasite powered sensor is detected before each
+//! conversion, it needs the strong pull-up and thereby the bus for the whole conversion. The
+//! config is taken over from every scratchpad that is read, so the wait matches the resolution the
+//! sensor has stored in its EEPROM.
+//!
+//! Errors are reduced to the [OneWireFault] that is shown in the status report, e.g.
+//! `sensor error: CRC mismatch`.
+//!
+//! A [Ds18b20Sensor] operates the sensor as a [Sensor] of the registry.
+
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, ThreadModeRawMutex};
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+
+use embassy_time::{Delay, Duration, Timer};
+
+use crate::cmd::{StatusReport, TemperatureReport};
+use crate::onewire::{OneWireAsyncMutex, OneWireBusError};
+use crate::sensor::{Reading, Sensor, SensorFault, SensorId, SensorSignal, SensorState};
+
+pub use nucleo_cmd::cmd::TemperatureSensorState;
+pub use nucleo_drivers::ds18b20::{Config as Ds18b20Config, Ds18b20, Resolution as Ds18b20Resolution};
+pub use nucleo_drivers::ds18b20::{TEMPERATURE_MAX, TEMPERATURE_MIN};
+pub use nucleo_drivers::error::OneWireFault;
+pub use nucleo_drivers::units::Celsius;
+
+/// The result of the last measurement, an error is kept such that it is shown in the status report
+pub type TemperatureSensorValueType = Result<Celsius, OneWireFault>;
+pub type SyncedTemperatureSensorValueType = Mutex<ThreadModeRawMutex, Option<TemperatureSensorValueType>>;
+
+pub type TemperatureSensorStateSync = Mutex<ThreadModeRawMutex, TemperatureSensorState>;
+pub type TemperatureSensorConfigSync = Mutex<ThreadModeRawMutex, Ds18b20Config>;
+pub type TemperatureSensorCollectSignal = Signal<CriticalSectionRawMutex, ()>;
+
+/// The state, configuration and last value of the DS18B20 that are shared between the tasks
+pub struct SharedTemperatureSensor {
+    pub state: TemperatureSensorStateSync,
+    pub config: TemperatureSensorConfigSync,
+    pub value: SyncedTemperatureSensorValueType,
+
+    /// interrupts the continuous measurement on a change of the state
+    pub signal: TemperatureSensorCollectSignal,
+}
+
+impl SharedTemperatureSensor {
+    /// A sensor without measurements in the power up resolution of 12 bit
+    pub const fn new() -> Self {
+        SharedTemperatureSensor {
+            state: TemperatureSensorStateSync::new(TemperatureSensorState::PowerOff),
+            config: TemperatureSensorConfigSync::new(Ds18b20Config {
+                resolution: Ds18b20Resolution::Bits12,
+                tl: TEMPERATURE_MIN,
+                th: TEMPERATURE_MAX,
+            }),
+            value: SyncedTemperatureSensorValueType::new(None),
+            signal: TemperatureSensorCollectSignal::new(),
+        }
+    }
+
+    /// Keeps the result of a measurement for queries and the status report, bus errors are kept too
+    pub async fn store(&self, res: &Result<Celsius, OneWireBusError>) {
+        let mut unlocked = self.value.lock().await;
+        *unlocked = Some(res.map_err(|err| err.fault()));
+    }
+
+    async fn set_state(&self, state: TemperatureSensorState) {
+        let mut unlocked = self.state.lock().await;
+        *unlocked = state;
+    }
+}
+
+impl Default for SharedTemperatureSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The time a conversion with the current config takes at most
+pub async fn conversion_time(sensor: &SharedTemperatureSensor) -> Duration {
+    let config = { *sensor.config.lock().await };
+    Duration::from_micros(config.conversion_time_us() as u64)
+}
+
+/// Converts and reads the temperature, the bus is released while the sensor converts
+pub async fn measure(sensor: &SharedTemperatureSensor, bus: &OneWireAsyncMutex) -> Result<Celsius, OneWireBusError> {
+    let config = { *sensor.config.lock().await };
+    let mut driver = Ds18b20::with_config(None, config);
+    let wait_us = {
+        let mut bus = bus.lock().await;
+        driver.read_power_supply(&mut *bus).await?;
+        driver.start_conversion(&mut *bus).await?
+    };
+    if wait_us > 0 {
+        Timer::after(Duration::from_micros(wait_us as u64)).await;
+    }
+
+    let res = {
+        let mut bus = bus.lock().await;
+        driver.read_temperature(&mut *bus).await
+    };
+    // the bus is unlocked first, set_resolution and set_alarm lock the config before the bus
+    if res.is_ok() {
+        let mut unlocked = sensor.config.lock().await;
+        *unlocked = driver.config();
+    }
+    res
+}
+
+pub async fn single_measurement(
+    sensor: &SharedTemperatureSensor,
+    bus: &OneWireAsyncMutex,
+) -> Result<Celsius, OneWireBusError> {
+    sensor.set_state(TemperatureSensorState::SingleMeasurement).await;
+    let res = measure(sensor, bus).await;
+    sensor.set_state(TemperatureSensorState::PowerOff).await;
+    res
+}
+
+/// Hands the measurements over to the task of the sensor, see [measure]
+pub async fn continuous_measurement(sensor: &SharedTemperatureSensor) {
+    sensor.set_state(TemperatureSensorState::ContinuousMeasurement).await;
+}
+
+/// Stops the measurements, the DS18B20 idles in a low power state between conversions anyway
+pub async fn power_off(sensor: &SharedTemperatureSensor) {
+    sensor.set_state(TemperatureSensorState::PowerOff).await;
+}
+
+/// Writes the config to the sensor and its EEPROM, the shared config is updated on success only
+async fn configure(
+    sensor: &SharedTemperatureSensor,
+    bus: &OneWireAsyncMutex,
+    change: impl FnOnce(&mut Ds18b20Config),
+) -> Result<(), OneWireBusError> {
+    let mut config = sensor.config.lock().await;
+    let mut changed = *config;
+    change(&mut changed);
+
+    let mut driver = Ds18b20::with_config(None, *config);
+    let mut bus = bus.lock().await;
+    driver.configure(&mut *bus, &mut Delay, changed).await?;
+    *config = driver.config();
+    Ok(())
+}
+
+/// Selects the resolution of the following conversions and persists it
+pub async fn set_resolution(
+    sensor: &SharedTemperatureSensor,
+    bus: &OneWireAsyncMutex,
+    resolution: Ds18b20Resolution,
+) -> Result<(), OneWireBusError> {
+    configure(sensor, bus, |config| config.resolution = resolution).await
+}
+
+/// Sets the alarm limits in °C and persists them
+pub async fn set_alarm(
+    sensor: &SharedTemperatureSensor,
+    bus: &OneWireAsyncMutex,
+    tl: i8,
+    th: i8,
+) -> Result<(), OneWireBusError> {
+    configure(sensor, bus, |config| {
+        config.tl = tl;
+        config.th = th;
+    })
+    .await
+}
+
+impl From<OneWireBusError> for SensorFault {
+    fn from(err: OneWireBusError) -> Self {
+        SensorFault::OneWire(err.fault())
+    }
+}
+
+/// The temperature sensor with the bus it is connected to, see [Sensor]
+#[derive(Clone, Copy)]
+pub struct Ds18b20Sensor {
+    shared: &'static SharedTemperatureSensor,
+    bus: &'static OneWireAsyncMutex,
+}
+
+impl Ds18b20Sensor {
+    pub fn new(shared: &'static SharedTemperatureSensor, bus: &'static OneWireAsyncMutex) -> Self {
+        Ds18b20Sensor { shared, bus }
+    }
+
+    pub fn shared(&self) -> &'static SharedTemperatureSensor {
+        self.shared
+    }
+}
+
+impl Sensor for Ds18b20Sensor {
+    fn id(&self) -> SensorId {
+        SensorId::Temperature
+    }
+
+    fn unit(&self) -> &'static str {
+        "°C"
+    }
+
+    async fn state(&self) -> SensorState {
+        let state = { *self.shared.state.lock().await };
+        state.into()
+    }
+
+    async fn start(&self) -> Result<(), SensorFault> {
+        continuous_measurement(self.shared).await;
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), SensorFault> {
+        power_off(self.shared).await;
+        Ok(())
+    }
+
+    async fn sample(&self) -> Result<Reading, SensorFault> {
+        let res = match self.state().await {
+            SensorState::Continuous => measure(self.shared, self.bus).await,
+            SensorState::Off | SensorState::Single => single_measurement(self.shared, self.bus).await,
+        };
+        self.shared.store(&res).await;
+        Ok(Reading::Temperature(res?))
+    }
+
+    async fn sample_interval(&self) -> Duration {
+        // a sample awaits the conversion itself
+        Duration::from_ticks(0)
+    }
+
+    fn signal(&self) -> &SensorSignal {
+        &self.shared.signal
+    }
+
+    async fn report(&self, report: &mut StatusReport) {
+        report.temperature = TemperatureReport {
+            state: *self.shared.state.lock().await,
+            value: *self.shared.value.lock().await,
+        };
+    }
+}
@@ -0,0 +1,314 @@
+//! Helpers for the shared I2C bus of the board
+//!
+//! The bus is shared between the tasks by an [I2cAsyncMutex]. Drivers do not lock it themselves,
+//! they are handed an [I2cDevice] instead. The handle implements the `I2c` trait of
+//! `embedded-hal-async`, locks the bus for each transaction and applies the [I2cDeviceConfig] of
+//! its device first, such that e.g. a fast mode part and a standard mode part share the bus.
+//!
+//! The raw transfers of [transfer] and [scan_bus] are used by the console to bring up new parts
+//! before there is a driver for them. A scan holds the lock for all probes, such that it is not
+//! interleaved with the continuous measurement of a light sensor.
+//!
+//! A device that is unplugged in the middle of a read may hold SDA low, such that every
+//! following transaction fails. The [RecoveringI2c] counts the bus errors in a row and recovers the bus
+//! after [RECOVERY_THRESHOLD] of them: the peripheral is dropped, [release_bus] clocks SCL until
+//! SDA is released and the peripheral is initialised again from its [I2cParts]. The recoveries are
+//! counted in [BUS_RECOVERIES].
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_stm32::gpio::{Level, OutputOpenDrain, Pin, Speed};
+use embassy_stm32::i2c::{Config as I2cConfig, Error as I2cError, ErrorInterruptHandler, EventInterruptHandler, I2c};
+use embassy_stm32::mode::Async;
+use embassy_stm32::peripherals::{DMA1_CH0, DMA1_CH6, I2C1, PB8, PB9};
+use embassy_stm32::time::Hertz;
+use embassy_stm32::{bind_interrupts, Peripheral};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+
+use embassy_embedded_hal::SetConfig;
+
+use embassy_time::{block_for, Duration};
+
+use embedded_hal_async::i2c::{ErrorType, I2c as AsyncI2c, Operation};
+
+use cortex_m_semihosting::hprintln;
+
+use nucleo_drivers::error::{i2c_fault, BusFault, BusFaultKind};
+use nucleo_drivers::scan::{self, Devices};
+
+/// The I2C bus of the board protected by Mutex
+pub type I2cAsyncMutex = Mutex<CriticalSectionRawMutex, RecoveringI2c>;
+
+/// The SCL clock of the bus, the peripheral is created with it and devices use it by default
+pub const DEFAULT_FREQUENCY: Hertz = Hertz(100_000);
+
+/// The number of bus errors in a row after which the bus is recovered, a NACK is no bus error
+pub const RECOVERY_THRESHOLD: u8 = 3;
+
+/// The number of bus recoveries since the start, it is shown in the status report
+pub static BUS_RECOVERIES: AtomicU32 = AtomicU32::new(0);
+
+bind_interrupts!(pub struct I2cIrqs {
+    I2C1_EV => EventInterruptHandler<I2C1>;
+    I2C1_ER => ErrorInterruptHandler<I2C1>;
+});
+
+/// The peripheral, pins and DMA channels of the I2C bus on the Arduino header
+pub struct I2cParts {
+    pub i2c: I2C1,
+    pub scl: PB8,
+    pub sda: PB9,
+    pub tx_dma: DMA1_CH6,
+    pub rx_dma: DMA1_CH0,
+}
+
+impl I2cParts {
+    /// Creates the peripheral at [DEFAULT_FREQUENCY], the previous one has to be dropped
+    ///
+    /// The `I2c` has to live as long as the mutex of the bus and does not give its parts back when
+    /// it is dropped, so it is created from copies of the parts kept here instead of a borrow.
+    fn create(&self) -> I2c<'static, Async> {
+        // SAFETY: the RecoveringI2c owns the parts and holds at most one peripheral created from
+        // them, it drops that before it creates the next one
+        unsafe {
+            I2c::new(
+                self.i2c.clone_unchecked(),
+                self.scl.clone_unchecked(),
+                self.sda.clone_unchecked(),
+                I2cIrqs,
+                self.tx_dma.clone_unchecked(),
+                self.rx_dma.clone_unchecked(),
+                DEFAULT_FREQUENCY,
+                I2cConfig::default(),
+            )
+        }
+    }
+}
+
+/// The I2C peripheral of the board that recovers the bus after repeated bus errors
+///
+/// A NACK only tells that a device did not answer, the bus itself works. Every other error counts,
+/// as a stuck SDA shows up as timeout, arbitration loss or bus error.
+pub struct RecoveringI2c {
+    i2c: Option<I2c<'static, Async>>,
+    parts: I2cParts,
+    errors_in_row: u8,
+    frequency: Hertz,
+}
+
+impl RecoveringI2c {
+    /// Creates the peripheral from the given parts, they are kept to create it again after a recovery
+    pub fn new(parts: I2cParts) -> Self {
+        RecoveringI2c {
+            i2c: Some(parts.create()),
+            parts,
+            errors_in_row: 0,
+            frequency: DEFAULT_FREQUENCY,
+        }
+    }
+
+    /// Changes the SCL clock, the timing is only written if it differs from the current one
+    pub fn set_frequency(&mut self, frequency: Hertz) {
+        if self.frequency != frequency {
+            // the timing of the I2C v2 peripheral is computed for any frequency, it never fails
+            let _ = self.i2c().set_config(&frequency);
+            self.frequency = frequency;
+        }
+    }
+
+    fn i2c(&mut self) -> &mut I2c<'static, Async> {
+        self.i2c.as_mut().expect("the peripheral is only missing during a recovery")
+    }
+
+    /// Counts the bus errors in a row and recovers the bus if there are too many
+    fn check<T>(&mut self, res: Result<T, I2cError>) -> Result<T, I2cError> {
+        match res {
+            Ok(_) | Err(I2cError::Nack) => self.errors_in_row = 0,
+            Err(_) => {
+                self.errors_in_row += 1;
+                if self.errors_in_row >= RECOVERY_THRESHOLD {
+                    self.recover();
+                }
+            }
+        }
+        res
+    }
+
+    /// Drops the peripheral, releases the bus and initialises the peripheral again
+    pub fn recover(&mut self) {
+        let attempt = BUS_RECOVERIES.fetch_add(1, Ordering::Relaxed) + 1;
+        hprintln!("I2C bus recovery #{} after {} bus errors in a row", attempt, self.errors_in_row);
+
+        self.i2c = None;
+        if !release_bus(&mut self.parts.scl, &mut self.parts.sda) {
+            hprintln!("SDA is still held low after the bus recovery");
+        }
+        self.i2c = Some(self.parts.create());
+        self.errors_in_row = 0;
+
+        let frequency = core::mem::replace(&mut self.frequency, DEFAULT_FREQUENCY);
+        self.set_frequency(frequency);
+    }
+}
+
+impl ErrorType for RecoveringI2c {
+    type Error = I2cError;
+}
+
+impl AsyncI2c for RecoveringI2c {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        let res = self.i2c().read(address, read).await;
+        self.check(res)
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        let res = self.i2c().write(address, write).await;
+        self.check(res)
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        let res = self.i2c().write_read(address, write, read).await;
+        self.check(res)
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        let res = self.i2c().transaction(address, operations).await;
+        self.check(res)
+    }
+}
+
+/// The bus configuration of one device, it is applied before each of its transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I2cDeviceConfig {
+    /// the SCL clock, e.g. 400kHz for a part that supports the fast mode
+    pub frequency: Hertz,
+}
+
+impl Default for I2cDeviceConfig {
+    fn default() -> Self {
+        I2cDeviceConfig {
+            frequency: DEFAULT_FREQUENCY,
+        }
+    }
+}
+
+/// The handle of one device on the shared bus, see the [module](self) documentation
+///
+/// The handle is cheap to create, a driver may be given a new one for every use.
+#[derive(Clone, Copy)]
+pub struct I2cDevice<'a> {
+    bus: &'a I2cAsyncMutex,
+    config: I2cDeviceConfig,
+}
+
+impl<'a> I2cDevice<'a> {
+    /// A device that uses the bus with the default configuration
+    pub fn new(bus: &'a I2cAsyncMutex) -> Self {
+        Self::with_config(bus, I2cDeviceConfig::default())
+    }
+
+    pub fn with_config(bus: &'a I2cAsyncMutex, config: I2cDeviceConfig) -> Self {
+        I2cDevice { bus, config }
+    }
+
+    pub fn config(&self) -> I2cDeviceConfig {
+        self.config
+    }
+
+    /// Locks the bus and configures it for this device
+    async fn lock(&self) -> MutexGuard<'a, CriticalSectionRawMutex, RecoveringI2c> {
+        let mut bus = self.bus.lock().await;
+        bus.set_frequency(self.config.frequency);
+        bus
+    }
+}
+
+impl ErrorType for I2cDevice<'_> {
+    type Error = I2cError;
+}
+
+impl AsyncI2c for I2cDevice<'_> {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock().await.read(address, read).await
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.lock().await.write(address, write).await
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock().await.write_read(address, write, read).await
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        self.lock().await.transaction(address, operations).await
+    }
+}
+
+/// Clocks SCL until the devices release SDA, at most nine times, and ends with a STOP condition
+///
+/// A device that has been interrupted while it sends a byte holds SDA low until it has been
+/// clocked through the remaining bits. The pins are driven as open-drain GPIO at 100kHz, they
+/// have to be free, e.g. the I2C peripheral has been dropped. Returns true if SDA is released.
+pub fn release_bus(scl: impl Peripheral<P = impl Pin>, sda: impl Peripheral<P = impl Pin>) -> bool {
+    let mut scl = OutputOpenDrain::new(scl, Level::High, Speed::Low);
+    let mut sda = OutputOpenDrain::new(sda, Level::High, Speed::Low);
+    let half_period = Duration::from_micros(5);
+
+    block_for(half_period);
+    for _ in 0..9 {
+        if sda.is_high() {
+            break;
+        }
+        scl.set_low();
+        block_for(half_period);
+        scl.set_high();
+        block_for(half_period);
+    }
+
+    // STOP condition: SDA rises while SCL is high
+    scl.set_low();
+    sda.set_low();
+    block_for(half_period);
+    scl.set_high();
+    block_for(half_period);
+    sda.set_high();
+    block_for(half_period);
+
+    sda.is_high()
+}
+
+/// Gives the kind of the error, unlike [i2c_fault] a timeout of the HAL is recognized
+pub fn bus_fault(addr: u8, error: &I2cError) -> BusFault {
+    match error {
+        I2cError::Timeout => BusFault {
+            kind: BusFaultKind::Timeout,
+            addr,
+        },
+        error => i2c_fault(error, addr),
+    }
+}
+
+/// Writes the bytes and then reads into the buffer with a repeated start, an empty part is skipped
+pub async fn transfer(i2c: &I2cAsyncMutex, addr: u8, write: &[u8], read: &mut [u8]) -> Result<(), BusFault> {
+    let mut device = I2cDevice::new(i2c);
+    let res = if read.is_empty() {
+        device.write(addr, write).await
+    } else if write.is_empty() {
+        device.read(addr, read).await
+    } else {
+        device.write_read(addr, write, read).await
+    };
+    res.map_err(|error| bus_fault(addr, &error))
+}
+
+/// Probes every address of the bus, the lock is held for the whole scan
+pub async fn scan_bus(i2c: &I2cAsyncMutex) -> Result<Devices, BusFault> {
+    let mut bus = i2c.lock().await;
+    bus.set_frequency(DEFAULT_FREQUENCY);
+    scan::scan(&mut *bus)
+        .await
+        .map_err(|err| bus_fault(err.addr, &err.error))
+}
@@ -15,26 +15,13 @@ use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 
 use embassy_futures::select::select;
 
+pub use nucleo_cmd::led::{str_to_led_state, LedState};
+
 pub type LedSignal = Signal<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, ()>;
 
 /// LedState protected by Mutex
 pub type LedStateSync = Mutex<ThreadModeRawMutex, LedState>;
 
-#[derive(Clone, Copy)]
-pub enum LedState {
-    /// the led is either on or off
-    Manual(bool),
-
-    /// the led toogles every specified amount of ms
-    Toggle(u32),
-}
-
-impl Default for LedState {
-    fn default() -> Self {
-        LedState::Manual(false)
-    }
-}
-
 /// Supports on/off/toggle(ms) useful for LEDs. 
 /// 
 /// 
@@ -60,19 +47,6 @@ pub async fn led_controller_simple(mut led: Output<'_>, synced_state: &LedStateS
     }
 }
 
-pub fn str_to_led_state(txt: &str) -> Option<LedState> {
-    match txt {
-        "off" => Some(LedState::Manual(false)),
-        "on" => Some(LedState::Manual(true)),
-        other => {
-            match other.parse() {
-                Ok(ms) => Some(LedState::Toggle(ms)),
-                Err(_) => None 
-            }
-        }
-    }
-}
-
 fn led_update_simple(led_out: &mut Output, state: &LedState) {
     match state {
         LedState::Manual(flag) => {
@@ -8,8 +8,21 @@
 //! - [x] LED handling
 //! - [x] UART Status Reports
 //! - [x] UART Command Interpretation
+//! - [x] BH1750FVI light sensor on the shared I2C bus
+//! - [x] Scanning the shared I2C bus
+//! - [x] Recovering the shared I2C bus when a device holds SDA low
+//! - [x] Per-device handles of the shared I2C bus with their own clock speed
+//! - [x] 1-Wire bus on an open-drain pin
+//! - [x] DS18B20 temperature sensor on the 1-Wire bus
+//! - [x] A common interface of the sensors and a registry of them
+//! - [x] Sampling the sensors at their own periods with a back off after faults
 
 pub mod bh1750fvi;
+pub mod ds18b20;
+pub mod i2c;
 pub mod led;
+pub mod onewire;
+pub mod scheduler;
+pub mod sensor;
 pub mod uart;
 pub mod cmd;
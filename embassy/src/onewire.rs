@@ -0,0 +1,164 @@
+//! Glue between the firmware and the 1-Wire bus of the board
+//!
+//! The board independent bus master is [OneWire] of the `nucleo_drivers` crate. Here it drives
+//! an open-drain pin, the pin needs an external pull-up of 4.7k. The slots need microseconds, a
+//! tick of the time driver is about 30µs, so [OneWireDelay] busy waits on the cycle counter of the
+//! core for them and uses the time driver for the longer waits only.
+//!
+//! Parasite powered slaves get a strong pull-up by switching the pin to push-pull while it is
+//! high, see [OneWirePin]. The 4.7k resistor cannot supply the 1.5mA of a conversion.
+//!
+//! The bus is shared between the tasks by a [OneWireAsyncMutex], a task holds the lock for a
+//! whole transaction from the reset to the last byte.
+
+use core::convert::Infallible;
+
+use embassy_stm32::gpio::{Flex, Pin, Speed};
+use embassy_stm32::time::Hertz;
+use embassy_stm32::Peripheral;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+
+use embassy_time::Delay;
+
+use embedded_hal::delay::DelayNs as BlockingDelayNs;
+use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+use embedded_hal_async::delay::DelayNs;
+
+use cortex_m::peripheral::{DCB, DWT};
+
+use heapless::Vec;
+
+use cortex_m_semihosting::hprintln;
+
+pub use nucleo_drivers::onewire::{OneWire, OneWireError, PowerMode, Rom, Search, StrongPullUp};
+
+/// The data line of the bus, open-drain unless the strong pull-up drives it high
+pub struct OneWirePin(Flex<'static>);
+
+impl OneWirePin {
+    /// Releases the bus to the pull-up resistor
+    pub fn new(pin: impl Peripheral<P = impl Pin> + 'static) -> Self {
+        let mut pin = Flex::new(pin);
+        pin.set_high();
+        pin.set_as_input_output(Speed::Low);
+        OneWirePin(pin)
+    }
+}
+
+impl ErrorType for OneWirePin {
+    type Error = Infallible;
+}
+
+impl InputPin for OneWirePin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.0.is_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.0.is_low())
+    }
+}
+
+impl OutputPin for OneWirePin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high();
+        Ok(())
+    }
+}
+
+impl StrongPullUp for OneWirePin {
+    fn set_strong_pull_up(&mut self, enable: bool) -> Result<(), Self::Error> {
+        // the output stays high, only the high side driver is switched
+        self.0.set_high();
+        if enable {
+            self.0.set_as_output(Speed::Low);
+        } else {
+            self.0.set_as_input_output(Speed::Low);
+        }
+        Ok(())
+    }
+}
+
+/// The delay of the bus, the slots busy wait on the cycle counter, the other waits use [Delay]
+#[derive(Debug, Clone, Copy)]
+pub struct OneWireDelay {
+    cycles_per_us: u32,
+}
+
+impl OneWireDelay {
+    /// Starts the cycle counter, the core runs with the given clock
+    pub fn new(dcb: &mut DCB, dwt: &mut DWT, sysclk: Hertz) -> Self {
+        dcb.enable_trace();
+        // the STM32F7 locks the DWT after a power cycle
+        DWT::unlock();
+        dwt.enable_cycle_counter();
+        OneWireDelay {
+            cycles_per_us: sysclk.0 / 1_000_000,
+        }
+    }
+}
+
+impl BlockingDelayNs for OneWireDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        let cycles = (ns as u64 * self.cycles_per_us as u64).div_ceil(1000);
+        let start = DWT::cycle_count();
+        while ((DWT::cycle_count().wrapping_sub(start)) as u64) < cycles {}
+    }
+}
+
+impl DelayNs for OneWireDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        DelayNs::delay_ns(&mut Delay, ns).await;
+    }
+}
+
+/// The 1-Wire master of the board
+pub type OneWireBus = OneWire<OneWirePin, OneWireDelay>;
+
+/// The 1-Wire bus of the board protected by Mutex
+pub type OneWireAsyncMutex = Mutex<CriticalSectionRawMutex, OneWireBus>;
+
+/// The errors of the 1-Wire bus, the pins of the board do not fail
+pub type OneWireBusError = OneWireError<Infallible>;
+
+/// The most devices a scan lists
+pub const ONEWIRE_MAX_DEVICES: usize = 8;
+
+/// Creates the master on the given pin, the bus is released
+pub fn new_bus(pin: impl Peripheral<P = impl Pin> + 'static, delay: OneWireDelay) -> OneWireBus {
+    OneWire::new(OneWirePin::new(pin), delay)
+}
+
+/// Enumerates the ROMs of the devices on the bus and how each is powered, a bus without devices
+/// gives none
+///
+/// [Search::all] gives every device, [Search::alarms] the ones with their alarm flag set.
+pub async fn scan_bus(
+    bus: &OneWireAsyncMutex,
+    mut search: Search,
+) -> Result<Vec<(Rom, PowerMode), ONEWIRE_MAX_DEVICES>, OneWireBusError> {
+    let mut bus = bus.lock().await;
+    let mut roms = Vec::new();
+    if !bus.reset().await? {
+        return Ok(roms);
+    }
+
+    while let Some(rom) = bus.search(&mut search).await? {
+        if roms.push((rom, PowerMode::External)).is_err() {
+            hprintln!("More than {} 1-Wire devices, the others are not listed", ONEWIRE_MAX_DEVICES);
+            break;
+        }
+    }
+    // asked after the search, as it selects a device in between
+    for (rom, power) in roms.iter_mut() {
+        *power = bus.power_mode(rom).await?;
+    }
+    Ok(roms)
+}
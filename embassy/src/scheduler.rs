@@ -0,0 +1,66 @@
+//! Samples the sensors of a [SensorRegistry] in continuous measurement at their own periods
+//!
+//! The function [run_scheduler] may be wrapped by an embassy task, it runs the schedule of every
+//! registered sensor concurrently, such that a slow conversion of one sensor does not delay the
+//! others. The schedule of a sensor
+//!
+//! - [x] samples it every [ScheduledSensor::period] while it is in continuous measurement, but
+//!   not before its [Sensor::sample_interval] has passed
+//! - [x] waits for its [Sensor::signal] otherwise
+//! - [x] starts over when the signal interrupts the wait for the next sample, e.g. after a change
+//!   of the state or period, a signal during a sample takes effect once it is done
+//! - [x] backs off after faults in a row, see [backoff_ms]
+
+use core::future::pending;
+
+use cortex_m_semihosting::hprintln;
+
+use embassy_futures::join::join_array;
+use embassy_futures::select::select;
+
+use embassy_time::{Duration, Instant, Timer};
+
+use nucleo_types::backoff::backoff_ms;
+
+use crate::sensor::{ScheduledSensor, Sensor, SensorRegistry, SensorState, SENSOR_CAPACITY};
+
+/// Runs the schedules of all sensors in the registry, never returns
+pub async fn run_scheduler(sensors: &SensorRegistry) {
+    let entries = sensors.entries();
+    let schedules: [_; SENSOR_CAPACITY] = core::array::from_fn(|idx| schedule(entries.get(idx)));
+    join_array(schedules).await;
+}
+
+async fn schedule(scheduled: Option<&ScheduledSensor>) {
+    let Some(scheduled) = scheduled else {
+        // a free place in the registry has nothing to sample
+        return pending().await;
+    };
+    let sensor = scheduled.sensor();
+    let signal = sensor.signal();
+    let mut faults = 0;
+    loop {
+        if sensor.state().await != SensorState::Continuous {
+            faults = 0;
+            signal.wait().await;
+            continue;
+        }
+
+        // the sample keeps its reading or fault for the status report, it is not raced against
+        // the signal, as a bus transaction that is dropped halfway may leave the bus in a bad state
+        let started = Instant::now();
+        match sensor.sample().await {
+            Ok(_) => faults = 0,
+            Err(fault) => {
+                faults += 1;
+                hprintln!("Sampling {} failed {} times in a row: {}", sensor.id(), faults, fault);
+            }
+        }
+
+        // a new reading is available once per conversion time, a signal ends the wait early but
+        // keeps the faults until a sample succeeds or the sensor leaves the continuous measurement
+        let due = started + Duration::from_millis(backoff_ms(scheduled.period_ms(), faults).into());
+        let ready = Instant::now() + sensor.sample_interval().await;
+        select(Timer::at(due.max(ready)), signal.wait()).await;
+    }
+}
@@ -0,0 +1,311 @@
+//! A common interface of the sensors of the firmware, see [Sensor]
+//!
+//! Every sensor is operated the same way: it is started for a continuous measurement, stopped,
+//! or sampled, which is a single measurement unless it has been started. The readings and faults
+//! are kept in the shared state of the sensor for queries and the status report.
+//!
+//! The sensors are kept in a [SensorRegistry] of fixed capacity, such that the command executor,
+//! the status report and the [scheduler](crate::scheduler) iterate over all of them. The registry
+//! keeps the period each sensor is sampled with in its continuous measurement. As the async
+//! functions of a trait cannot be called on a trait object without an allocator, the registry
+//! holds an [AnySensor] that dispatches to the implementations:
+//!
+//! - [x] BH1750FVI light sensor, see [Bh1750Sensor]
+//! - [x] DS18B20 temperature sensor, see [Ds18b20Sensor]
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+use embassy_time::Duration;
+
+use heapless::Vec;
+
+use crate::bh1750fvi::Bh1750Sensor;
+use crate::cmd::{Response, StatusReport};
+use crate::ds18b20::Ds18b20Sensor;
+
+pub use nucleo_cmd::cmd::{LightSensorState, SensorId, TemperatureSensorState};
+pub use nucleo_drivers::error::{BusFault, OneWireFault};
+pub use nucleo_drivers::units::{Celsius, Lux};
+
+/// The most sensors a [SensorRegistry] holds
+pub const SENSOR_CAPACITY: usize = 4;
+
+/// Interrupts the continuous measurement of a sensor on a change of its state or period
+pub type SensorSignal = Signal<CriticalSectionRawMutex, ()>;
+
+/// The state a sensor is operated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorState {
+    Off,
+    Single,
+    Continuous,
+}
+
+impl From<LightSensorState> for SensorState {
+    fn from(state: LightSensorState) -> Self {
+        match state {
+            LightSensorState::PowerOff => SensorState::Off,
+            LightSensorState::SingleMeasurement => SensorState::Single,
+            LightSensorState::ContiniousMeasurement => SensorState::Continuous,
+        }
+    }
+}
+
+impl From<TemperatureSensorState> for SensorState {
+    fn from(state: TemperatureSensorState) -> Self {
+        match state {
+            TemperatureSensorState::PowerOff => SensorState::Off,
+            TemperatureSensorState::SingleMeasurement => SensorState::Single,
+            TemperatureSensorState::ContinuousMeasurement => SensorState::Continuous,
+        }
+    }
+}
+
+/// A reading of one of the sensors, the light sensors are told apart by their id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reading {
+    Light(u8, Lux),
+    Temperature(Celsius),
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reading::Light(_, lux) => write!(f, "{}", lux),
+            Reading::Temperature(celsius) => write!(f, "{}", celsius),
+        }
+    }
+}
+
+impl From<Reading> for Response {
+    fn from(reading: Reading) -> Self {
+        match reading {
+            Reading::Light(id, lux) => Response::LightValue(id, lux),
+            Reading::Temperature(celsius) => Response::TemperatureValue(celsius),
+        }
+    }
+}
+
+/// Why a sensor could not be operated, as it is shown to the operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorFault {
+    /// a transaction on the I2C bus failed
+    I2c(BusFault),
+
+    /// a transaction on the 1-Wire bus failed
+    OneWire(OneWireFault),
+
+    /// the driver refused its configuration, the bus has not been used
+    Config,
+}
+
+impl fmt::Display for SensorFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorFault::I2c(fault) => write!(f, "{}", fault),
+            SensorFault::OneWire(fault) => write!(f, "{}", fault),
+            SensorFault::Config => f.write_str("invalid configuration"),
+        }
+    }
+}
+
+/// A sensor together with the bus it is connected to
+///
+/// The functions lock the state of the sensor and its bus as needed. The [Sensor::signal] tells
+/// the scheduler that the state changed, a sample is not interrupted by it.
+#[allow(async_fn_in_trait)]
+pub trait Sensor {
+    fn id(&self) -> SensorId;
+
+    /// The unit of the readings, e.g. `Lux`
+    fn unit(&self) -> &'static str;
+
+    async fn state(&self) -> SensorState;
+
+    /// Starts the continuous measurement, its readings are taken with [Sensor::sample]
+    async fn start(&self) -> Result<(), SensorFault>;
+
+    /// Stops the measurements, a sensor that supports it is powered down
+    async fn stop(&self) -> Result<(), SensorFault>;
+
+    /// Takes a reading and keeps it, a sensor that has not been started does a single measurement
+    async fn sample(&self) -> Result<Reading, SensorFault>;
+
+    /// The time the next reading of the continuous measurement takes to be available after a
+    /// sample, zero if the sample waits for the conversion itself
+    async fn sample_interval(&self) -> Duration;
+
+    fn signal(&self) -> &SensorSignal;
+
+    /// Fills in the state and last reading of the sensor
+    async fn report(&self, report: &mut StatusReport);
+}
+
+/// One of the sensors of the firmware, see the [module](self) documentation
+#[derive(Clone, Copy)]
+pub enum AnySensor {
+    Bh1750(Bh1750Sensor),
+    Ds18b20(Ds18b20Sensor),
+}
+
+impl Sensor for AnySensor {
+    fn id(&self) -> SensorId {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.id(),
+            AnySensor::Ds18b20(sensor) => sensor.id(),
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.unit(),
+            AnySensor::Ds18b20(sensor) => sensor.unit(),
+        }
+    }
+
+    async fn state(&self) -> SensorState {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.state().await,
+            AnySensor::Ds18b20(sensor) => sensor.state().await,
+        }
+    }
+
+    async fn start(&self) -> Result<(), SensorFault> {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.start().await,
+            AnySensor::Ds18b20(sensor) => sensor.start().await,
+        }
+    }
+
+    async fn stop(&self) -> Result<(), SensorFault> {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.stop().await,
+            AnySensor::Ds18b20(sensor) => sensor.stop().await,
+        }
+    }
+
+    async fn sample(&self) -> Result<Reading, SensorFault> {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.sample().await,
+            AnySensor::Ds18b20(sensor) => sensor.sample().await,
+        }
+    }
+
+    async fn sample_interval(&self) -> Duration {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.sample_interval().await,
+            AnySensor::Ds18b20(sensor) => sensor.sample_interval().await,
+        }
+    }
+
+    fn signal(&self) -> &SensorSignal {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.signal(),
+            AnySensor::Ds18b20(sensor) => sensor.signal(),
+        }
+    }
+
+    async fn report(&self, report: &mut StatusReport) {
+        match self {
+            AnySensor::Bh1750(sensor) => sensor.report(report).await,
+            AnySensor::Ds18b20(sensor) => sensor.report(report).await,
+        }
+    }
+}
+
+impl From<Bh1750Sensor> for AnySensor {
+    fn from(sensor: Bh1750Sensor) -> Self {
+        AnySensor::Bh1750(sensor)
+    }
+}
+
+impl From<Ds18b20Sensor> for AnySensor {
+    fn from(sensor: Ds18b20Sensor) -> Self {
+        AnySensor::Ds18b20(sensor)
+    }
+}
+
+/// A registered sensor together with the period in ms it is sampled with
+pub struct ScheduledSensor {
+    sensor: AnySensor,
+    period_ms: AtomicU32,
+}
+
+impl ScheduledSensor {
+    pub fn sensor(&self) -> &AnySensor {
+        &self.sensor
+    }
+
+    pub fn period_ms(&self) -> u32 {
+        self.period_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms().into())
+    }
+
+    /// Changes the period, the signal of the sensor lets the scheduler follow at once
+    pub fn set_period(&self, period_ms: u32) {
+        self.period_ms.store(period_ms, Ordering::Relaxed);
+        self.sensor.signal().signal(());
+    }
+}
+
+/// The sensors of the firmware in the order they are registered, at most [SENSOR_CAPACITY]
+pub struct SensorRegistry {
+    sensors: Vec<ScheduledSensor, SENSOR_CAPACITY>,
+}
+
+impl SensorRegistry {
+    pub const fn new() -> Self {
+        SensorRegistry { sensors: Vec::new() }
+    }
+
+    /// Adds the sensor with its sampling period, it is given back if the registry is full or has
+    /// a sensor with its id
+    pub fn register(&mut self, sensor: impl Into<AnySensor>, period_ms: u32) -> Result<(), AnySensor> {
+        let sensor = sensor.into();
+        if self.get(sensor.id()).is_some() {
+            return Err(sensor);
+        }
+        self.sensors
+            .push(ScheduledSensor { sensor, period_ms: AtomicU32::new(period_ms) })
+            .map_err(|scheduled| scheduled.sensor)
+    }
+
+    pub fn get(&self, id: SensorId) -> Option<&AnySensor> {
+        self.scheduled(id).map(ScheduledSensor::sensor)
+    }
+
+    /// Gives the sensor with the given id together with its sampling period
+    pub fn scheduled(&self, id: SensorId) -> Option<&ScheduledSensor> {
+        self.sensors.iter().find(|scheduled| scheduled.sensor.id() == id)
+    }
+
+    /// All sensors with their sampling periods by their index of registration
+    pub fn entries(&self) -> &[ScheduledSensor] {
+        &self.sensors
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AnySensor> {
+        self.sensors.iter().map(ScheduledSensor::sensor)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sensors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty()
+    }
+}
+
+impl Default for SensorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,10 +1,29 @@
+//! Helpers for the UART based developer console
+//!
+//! The transmitting half of the console is shared between tasks by a [UartTxMutex], such that the
+//! echo of the [ConsoleReader] and the responses of the command executor do not interleave.
+//!
+//! The console either speaks lines of text or the binary frames of [nucleo_cmd::frame].
+
+use core::fmt::Write;
+
 use heapless::String;
 
 use embassy_stm32::mode::Async;
-use embassy_stm32::usart::UartRx;
+use embassy_stm32::usart::{Error as UsartError, UartRx, UartTx};
+
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::mutex::Mutex;
 
 use cortex_m_semihosting::hprintln;
 
+use nucleo_cmd::cmd::ConsoleMode;
+use nucleo_cmd::frame::{self, FrameReader, Reply, Request, MAX_FRAME_LEN};
+use nucleo_cmd::line::LineEditor;
+
+/// The transmitting half of the developer console protected by Mutex
+pub type UartTxMutex = Mutex<ThreadModeRawMutex, UartTx<'static, Async>>;
+
 #[macro_export]
 macro_rules! setup_usart_developer_console {
     ($p:ident, $irqs:ident, $config:expr) => {
@@ -21,30 +40,130 @@ macro_rules! setup_usart_developer_console {
     }
 }
 
-pub async fn parse_uart_tx_as_utf8<'a, const N: usize>(
-    usart_rx: &mut UartRx<'static, Async>, 
-    buf: &'a mut [u8; N]) 
-    -> Result<&'a str, String<64>> 
-{
-    let res = usart_rx.read_until_idle(buf).await;
-    match res {
-        Ok(len) => {
-            match core::str::from_utf8(&buf[..len]) {
-                Ok(msg) => {
-                    Ok(msg.trim())
-                },
-                Err(_err) => {
-                    hprintln!("Received invalid utf-8 over USART, ignore transmission");
-                    //Err(err.into())
-                    Err("Received invalid utf-8 over USART, ignore transmission".try_into().unwrap())
-                },
+/// Reads from the console either lines of at most `N` characters or binary frames
+///
+/// The bytes of a DMA transfer are handed to a [LineEditor] or a [FrameReader] one by one, such
+/// that a line or frame may be assembled across any number of transfers and one transfer may
+/// contain several of them. Bytes following a `mode` command in the same transfer are therefore
+/// interpreted in the new mode. The last `H` lines are remembered by the [LineEditor].
+pub struct ConsoleReader<const N: usize, const H: usize> {
+    editor: LineEditor<N, H>,
+    frames: FrameReader,
+    buf: [u8; 32],
+    pos: usize,
+    len: usize,
+}
+
+impl<const N: usize, const H: usize> Default for ConsoleReader<N, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const H: usize> ConsoleReader<N, H> {
+    pub const fn new() -> Self {
+        ConsoleReader {
+            editor: LineEditor::new(),
+            frames: FrameReader::new(),
+            buf: [0; 32],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Waits for the next complete line, the echo is written to `usart_tx` while typing
+    pub async fn read_line(
+        &mut self,
+        usart_rx: &mut UartRx<'static, Async>,
+        usart_tx: &UartTxMutex,
+    ) -> Result<String<N>, String<64>> {
+        loop {
+            while self.pos < self.len {
+                let byte = self.buf[self.pos];
+                self.pos += 1;
+
+                let line = self.editor.feed(byte);
+                if line.is_some() || self.pos == self.len {
+                    // the echo has to reach the terminal before any response to the line
+                    self.flush_echo(usart_tx).await;
+                }
+                if let Some(line) = line {
+                    return Ok(line);
+                }
             }
+
+            self.fill(usart_rx).await?;
         }
-        Err(_err) => {
-            // todo: more error infos
-            hprintln!("USART related error, ignore transmission");
-            //Err(err.into())
-            Err("USART related error, ignore transmission".try_into().unwrap())
+    }
+
+    /// Waits for the next complete binary frame
+    pub async fn read_frame(&mut self, usart_rx: &mut UartRx<'static, Async>) -> Result<Request, String<64>> {
+        loop {
+            while self.pos < self.len {
+                let byte = self.buf[self.pos];
+                self.pos += 1;
+
+                match self.frames.feed(byte) {
+                    Some(Ok(request)) => return Ok(request),
+                    Some(Err(err)) => {
+                        let mut reason = String::new();
+                        let _ = core::write!(&mut reason, "{}", err);
+                        return Err(reason);
+                    }
+                    None => {}
+                }
+            }
+
+            self.fill(usart_rx).await?;
+        }
+    }
+
+    async fn fill(&mut self, usart_rx: &mut UartRx<'static, Async>) -> Result<(), String<64>> {
+        self.pos = 0;
+        self.len = 0;
+        match usart_rx.read_until_idle(&mut self.buf).await {
+            Ok(len) => {
+                self.len = len;
+                Ok(())
+            }
+            Err(_err) => {
+                // todo: more error infos
+                hprintln!("USART related error, ignore transmission");
+                Err("USART related error, ignore transmission".try_into().unwrap())
+            }
+        }
+    }
+
+    async fn flush_echo(&mut self, usart_tx: &UartTxMutex) {
+        if !self.editor.echo().is_empty() {
+            let mut unlocked = usart_tx.lock().await;
+            // losing the echo is not critical, the line is processed anyway
+            let _ = unlocked.write(self.editor.echo()).await;
         }
+        self.editor.clear_echo();
     }
-}
\ No newline at end of file
+}
+
+/// Writes a reply to the console, either as text line or as binary frame
+pub async fn write_reply(usart_tx: &UartTxMutex, reply: &Reply, mode: ConsoleMode) -> Result<(), UsartError> {
+    match mode {
+        ConsoleMode::Text => {
+            // a response that does not fit into the buffer is truncated
+            let mut msg: String<160> = String::new();
+            let _ = core::write!(&mut msg, "{}", reply.payload);
+            let mut unlocked = usart_tx.lock().await;
+            unlocked.write(msg.as_bytes()).await?;
+            unlocked.write(b"\r\n").await
+        }
+        ConsoleMode::Binary => {
+            let mut wire = [0u8; MAX_FRAME_LEN];
+            match frame::encode(reply, &mut wire) {
+                Ok(len) => usart_tx.lock().await.write(&wire[..len]).await,
+                Err(_err) => {
+                    hprintln!("Reply does not fit into a frame, it is dropped");
+                    Ok(())
+                }
+            }
+        }
+    }
+}
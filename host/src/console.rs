@@ -0,0 +1,254 @@
+//! A session with the text console of the firmware, see [Console].
+//!
+//! The console of the firmware echoes every line, answers each command with zero or more value
+//! lines followed by `OK <cmd>` or `ERR <reason>` and sends a status report periodically. The
+//! [Console] removes the echo and classifies the received lines as [Line]s.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use nucleo_cmd::{str_to_command, Commands, StatusReport};
+
+/// A line received from the firmware
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    /// a periodic status report
+    Status(StatusReport),
+
+    /// a value as answer to a command, without the `VAL` prefix
+    Value(String),
+
+    /// the command has been executed, the acknowledged command is given
+    Ok(String),
+
+    /// the command failed for the given reason
+    Err(String),
+
+    /// any other output, e.g. the usage lines of `help`
+    Output(String),
+}
+
+impl Line {
+    pub fn parse(line: &str) -> Self {
+        if let Ok(report) = line.parse() {
+            Line::Status(report)
+        } else if let Some(value) = line.strip_prefix("VAL ") {
+            Line::Value(value.to_string())
+        } else if let Some(cmd) = line.strip_prefix("OK ") {
+            Line::Ok(cmd.to_string())
+        } else if let Some(reason) = line.strip_prefix("ERR ") {
+            Line::Err(reason.to_string())
+        } else {
+            Line::Output(line.to_string())
+        }
+    }
+
+    /// Indicates if this is the last line of the answer to a command
+    pub fn is_final(&self) -> bool {
+        matches!(self, Line::Ok(_) | Line::Err(_))
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Line::Status(report) => write!(f, "{}", report),
+            Line::Value(value) => write!(f, "VAL {}", value),
+            Line::Ok(cmd) => write!(f, "OK {}", cmd),
+            Line::Err(reason) => write!(f, "ERR {}", reason),
+            Line::Output(line) => f.write_str(line),
+        }
+    }
+}
+
+/// The answer of the firmware to a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Answer {
+    /// the command as it has been sent
+    pub command: Commands,
+
+    /// the value and output lines received before the final line
+    pub values: Vec<Line>,
+
+    /// the acknowledged command or the reason of the failure
+    pub result: Result<String, String>,
+}
+
+#[derive(Debug)]
+pub enum ConsoleError {
+    /// the command is rejected before it is sent
+    Parse(String),
+
+    /// the given command has not been answered in time
+    Timeout(Commands),
+
+    /// reading or writing the port failed
+    Io(io::Error),
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleError::Parse(reason) => write!(f, "invalid command: {}", reason),
+            ConsoleError::Timeout(cmd) => write!(f, "no answer to '{}'", cmd),
+            ConsoleError::Io(err) => write!(f, "serial port error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConsoleError {}
+
+impl From<io::Error> for ConsoleError {
+    fn from(err: io::Error) -> Self {
+        ConsoleError::Io(err)
+    }
+}
+
+/// A session with the text console of the firmware over the given port
+///
+/// Reads from the port are expected to time out after a short while, as a serial port does, such
+/// that [Console::next_line] returns regularly.
+pub struct Console<P> {
+    port: P,
+    timeout: Duration,
+    received: Vec<u8>,
+    pending_echo: VecDeque<String>,
+}
+
+impl<P: Read + Write> Console<P> {
+    /// Creates a session that waits at most `timeout` for the answer to a command
+    pub fn new(port: P, timeout: Duration) -> Self {
+        Console {
+            port,
+            timeout,
+            received: Vec::new(),
+            pending_echo: VecDeque::new(),
+        }
+    }
+
+    /// Parses the given line and sends it in canonical form, the parsed command is returned
+    pub fn send(&mut self, line: &str) -> Result<Commands, ConsoleError> {
+        let cmd = str_to_command(line).map_err(|err| ConsoleError::Parse(err.to_string()))?;
+
+        let text = cmd.to_string();
+        self.port.write_all(text.as_bytes())?;
+        self.port.write_all(b"\r\n")?;
+        self.port.flush()?;
+        self.pending_echo.push_back(text);
+        Ok(cmd)
+    }
+
+    /// Gives the next received line that is not the echo of a sent command
+    ///
+    /// None is returned if nothing has been received before the port timed out.
+    pub fn next_line(&mut self) -> io::Result<Option<Line>> {
+        loop {
+            if let Some(pos) = self.received.iter().position(|&byte| byte == b'\n') {
+                let raw: Vec<u8> = self.received.drain(..=pos).collect();
+                let text = String::from_utf8_lossy(&raw);
+                let text = text.trim_end_matches(['\r', '\n']);
+
+                if text.is_empty() {
+                    continue;
+                }
+                if self.pending_echo.front().map(|echo| echo == text).unwrap_or(false) {
+                    self.pending_echo.pop_front();
+                    continue;
+                }
+                return Ok(Some(Line::parse(text)));
+            }
+
+            let mut buf = [0u8; 64];
+            match self.port.read(&mut buf) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.received.extend_from_slice(&buf[..len]),
+                Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => return Ok(None),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Sends the command given as line and collects its answer
+    ///
+    /// Status reports received in the meantime are handed to `on_report`.
+    pub fn execute(
+        &mut self,
+        line: &str,
+        on_report: &mut dyn FnMut(&StatusReport),
+    ) -> Result<Answer, ConsoleError> {
+        let command = self.send(line)?;
+        let deadline = Instant::now() + self.timeout;
+
+        let mut values = Vec::new();
+        while Instant::now() < deadline {
+            match self.next_line()? {
+                Some(Line::Status(report)) => on_report(&report),
+                Some(Line::Ok(cmd)) => {
+                    return Ok(Answer {
+                        command,
+                        values,
+                        result: Ok(cmd),
+                    })
+                }
+                Some(Line::Err(reason)) => {
+                    return Ok(Answer {
+                        command,
+                        values,
+                        result: Err(reason),
+                    })
+                }
+                Some(line) => values.push(line),
+                None => {}
+            }
+        }
+
+        // the echo will not be recognized once the next command has been sent
+        self.pending_echo.clear();
+        Err(ConsoleError::Timeout(command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nucleo_cmd::{BusFault, Celsius, LightReport, LightSensorState, Lux, TemperatureReport, TemperatureSensorState};
+
+    #[test]
+    fn classifies_lines() {
+        assert_eq!(Line::parse("VAL light 1 12.0"), Line::Value("light 1 12.0".to_string()));
+        assert_eq!(Line::parse("OK led r on"), Line::Ok("led r on".to_string()));
+        assert_eq!(
+            Line::parse("ERR 'x' is not a valid number"),
+            Line::Err("'x' is not a valid number".to_string())
+        );
+        assert_eq!(
+            Line::parse(
+                "Status: Light Sensor 1 single - 7.5 Lux, Light Sensor 2 on - sensor error: NACK @0x5C, \
+                 Temperature on - -3.50 °C, I2C recoveries 1"
+            ),
+            Line::Status(StatusReport {
+                light: [
+                    LightReport {
+                        state: LightSensorState::SingleMeasurement,
+                        value: Some(Ok(Lux::from_millilux(7500))),
+                    },
+                    LightReport {
+                        state: LightSensorState::ContiniousMeasurement,
+                        value: Some(Err("NACK @0x5C".parse::<BusFault>().unwrap())),
+                    },
+                ],
+                temperature: TemperatureReport {
+                    state: TemperatureSensorState::ContinuousMeasurement,
+                    value: Some(Ok(Celsius::from_millidegrees(-3_500))),
+                },
+                bus_recoveries: 1,
+            })
+        );
+        assert_eq!(Line::parse("help|? [<command>]"), Line::Output("help|? [<command>]".to_string()));
+        assert!(Line::parse("OK state").is_final());
+        assert!(!Line::parse("VAL status 10000").is_final());
+    }
+}
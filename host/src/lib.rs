@@ -0,0 +1,16 @@
+//! The host side companion of the sensor platform, it replaces typing commands into PuTTY.
+//!
+//! The tool talks to the text console of the firmware over a serial port. Commands are parsed
+//! and printed with [nucleo_cmd], such that the host and the firmware share a single grammar:
+//!
+//! - [x] Validation of commands before they are sent
+//! - [x] Collection of the answer of a command up to its `OK` or `ERR` line
+//! - [x] Parsed status reports that are shown while waiting for answers
+//! - [x] Script mode for regression runs against the board
+//!
+//! The [Console] is generic over [Read](std::io::Read) and [Write](std::io::Write), its tests
+//! run against a pseudo terminal instead of the board.
+
+pub mod console;
+
+pub use console::{Answer, Console, ConsoleError, Line};
@@ -0,0 +1,193 @@
+//! Command line companion of the sensor platform
+//!
+//! ```text
+//! nucleo-cli <port> [--baud <rate>] [--timeout <ms>] [--script <file>]
+//! ```
+//!
+//! Without a script the commands are read from stdin and everything the board sends is shown,
+//! with a script every line is sent as command and the tool exits with a failure on the first
+//! command that is rejected or not answered in time. `-` reads the script from stdin.
+
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use nucleo_cli::{Console, ConsoleError, Line};
+use nucleo_cmd::StatusReport;
+
+const USAGE: &str = "usage: nucleo-cli <port> [--baud <rate>] [--timeout <ms>] [--script <file>]";
+
+struct Options {
+    port: String,
+    baud: u32,
+    timeout: Duration,
+    script: Option<String>,
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut port = None;
+    let mut baud = 115200;
+    let mut timeout = Duration::from_millis(2000);
+    let mut script = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value of {}", name));
+        match arg.as_str() {
+            "--baud" => {
+                let rate = value("--baud")?;
+                baud = rate.parse().map_err(|_| format!("'{}' is not a valid baud rate", rate))?;
+            }
+            "--timeout" => {
+                let ms = value("--timeout")?;
+                let ms = ms.parse().map_err(|_| format!("'{}' is not a valid number", ms))?;
+                timeout = Duration::from_millis(ms);
+            }
+            "--script" => script = Some(value("--script")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if port.is_none() => port = Some(arg),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+
+    Ok(Options {
+        port: port.ok_or(USAGE.to_string())?,
+        baud,
+        timeout,
+        script,
+    })
+}
+
+fn print_report(report: &StatusReport) {
+    let lights: Vec<String> = report
+        .light
+        .iter()
+        .enumerate()
+        .map(|(idx, light)| {
+            let value = match light.value {
+                Some(Ok(value)) => format!("{} lux", value),
+                Some(Err(fault)) => format!("sensor error: {}", fault),
+                None => "no value yet".to_string(),
+            };
+            format!("light sensor {} {}, {}", idx + 1, light.state.as_str(), value)
+        })
+        .collect();
+    let temperature = match report.temperature.value {
+        Some(Ok(value)) => format!("{} °C", value),
+        Some(Err(fault)) => format!("sensor error: {}", fault),
+        None => "no value yet".to_string(),
+    };
+    println!(
+        "[status] {}; temperature {}, {}; i2c recoveries {}",
+        lights.join("; "),
+        report.temperature.state.as_str(),
+        temperature,
+        report.bus_recoveries
+    );
+}
+
+fn print_line(line: &Line) {
+    match line {
+        Line::Status(report) => print_report(report),
+        line => println!("{}", line),
+    }
+}
+
+/// Sends every line of the script, empty lines and lines starting with # are skipped
+fn run_script<P: Read + io::Write>(console: &mut Console<P>, script: &str) -> Result<(), String> {
+    for (idx, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        println!("> {}", line);
+        let answer = console
+            .execute(line, &mut print_report)
+            .map_err(|err| format!("line {}: {}", idx + 1, err))?;
+        answer.values.iter().for_each(print_line);
+        match answer.result {
+            Ok(cmd) => println!("OK {}", cmd),
+            Err(reason) => return Err(format!("line {}: ERR {}", idx + 1, reason)),
+        }
+    }
+    Ok(())
+}
+
+/// Forwards the lines typed on stdin and shows everything the board sends
+fn run_interactive<P: Read + io::Write>(console: &mut Console<P>) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        match receiver.try_recv() {
+            Ok(line) if line.trim().is_empty() => {}
+            Ok(line) => match console.send(&line) {
+                Ok(_) => {}
+                Err(ConsoleError::Parse(reason)) => eprintln!("not sent, {}", reason),
+                Err(err) => return Err(err.to_string()),
+            },
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => return Ok(()),
+        }
+
+        // returns after the read timeout of the port, such that stdin is polled regularly
+        if let Some(line) = console.next_line().map_err(|err| err.to_string())? {
+            print_line(&line);
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            return ExitCode::from(2);
+        }
+    };
+
+    let port = serialport::new(&options.port, options.baud)
+        .timeout(Duration::from_millis(50))
+        .open();
+    let port = match port {
+        Ok(port) => port,
+        Err(err) => {
+            eprintln!("cannot open {}: {}", options.port, err);
+            return ExitCode::from(2);
+        }
+    };
+    let mut console = Console::new(port, options.timeout);
+
+    let result = match &options.script {
+        Some(path) => {
+            let script = if path == "-" {
+                io::read_to_string(io::stdin())
+            } else {
+                fs::read_to_string(path)
+            };
+            match script {
+                Ok(script) => run_script(&mut console, &script),
+                Err(err) => Err(format!("cannot read {}: {}", path, err)),
+            }
+        }
+        None => run_interactive(&mut console),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            ExitCode::FAILURE
+        }
+    }
+}
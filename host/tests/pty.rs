@@ -0,0 +1,149 @@
+//! Runs the console against a fake board on the other end of a pseudo terminal
+#![cfg(unix)]
+
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use serialport::{SerialPort, TTYPort};
+
+use nucleo_cli::{Console, ConsoleError, Line};
+use nucleo_cmd::{
+    str_to_command, Commands, LightReport, LightSensorCommands, LightSensorState, LineEditor, Lux, StatusReport,
+    TemperatureReport, TemperatureSensorState,
+};
+
+const REPORT: StatusReport = StatusReport {
+    light: [
+        LightReport {
+            state: LightSensorState::SingleMeasurement,
+            value: Some(Ok(Lux::from_lux(42))),
+        },
+        LightReport {
+            state: LightSensorState::PowerOff,
+            value: None,
+        },
+    ],
+    temperature: TemperatureReport {
+        state: TemperatureSensorState::PowerOff,
+        value: None,
+    },
+    bus_recoveries: 0,
+};
+
+/// Answers like the firmware, a status report precedes every answer and `mode` is never answered
+///
+/// `status 0` is rejected to test the failure of a command.
+fn fake_board(mut port: TTYPort) {
+    let mut editor: LineEditor<64, 1> = LineEditor::new();
+    let mut buf = [0u8; 64];
+    loop {
+        let len = match port.read(&mut buf) {
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::TimedOut => continue,
+            // the other end has been closed
+            Err(_) => return,
+        };
+
+        for &byte in &buf[..len] {
+            let line = editor.feed(byte);
+            port.write_all(editor.echo()).unwrap();
+            editor.clear_echo();
+
+            let answer = match line.as_deref().map(str_to_command) {
+                None | Some(Ok(Commands::Mode(_))) => continue,
+                Some(Ok(Commands::LightSensor(1, LightSensorCommands::SingleMeasurment))) => {
+                    "VAL light 1 42.0\r\nOK light 1 single".to_string()
+                }
+                Some(Ok(Commands::UartStatusReport(0))) => "ERR interval must not be 0".to_string(),
+                Some(Ok(cmd)) => format!("OK {}", cmd),
+                Some(Err(err)) => format!("ERR {}", err),
+            };
+            write!(port, "{}\r\n{}\r\n", REPORT, answer).unwrap();
+        }
+    }
+}
+
+/// Starts a fake board and gives the path of the terminal it is connected to
+fn start_board() -> (TTYPort, String) {
+    let (board, host) = TTYPort::pair().expect("pseudo terminals are supported");
+    let path = host.name().unwrap();
+    thread::spawn(move || fake_board(board));
+    (host, path)
+}
+
+#[test]
+fn collects_answers_and_reports() {
+    let (port, _) = start_board();
+    let mut console = Console::new(port, Duration::from_secs(2));
+    let mut reports = Vec::new();
+
+    let answer = console.execute("led  r on", &mut |report| reports.push(*report)).unwrap();
+    assert_eq!(answer.result, Ok("led r on".to_string()));
+    assert!(answer.values.is_empty(), "the echo is removed");
+
+    let answer = console.execute("light indoor s", &mut |report| reports.push(*report)).unwrap();
+    assert_eq!(answer.values, [Line::Value("light 1 42.0".to_string())]);
+    assert_eq!(answer.result, Ok("light 1 single".to_string()));
+
+    let answer = console.execute("status 0", &mut |report| reports.push(*report)).unwrap();
+    assert_eq!(answer.result, Err("interval must not be 0".to_string()));
+
+    assert_eq!(reports, [REPORT, REPORT, REPORT]);
+}
+
+#[test]
+fn invalid_commands_are_not_sent() {
+    let (port, _) = start_board();
+    let mut console = Console::new(port, Duration::from_secs(2));
+
+    let result = console.execute("led x on", &mut |_| {});
+    assert!(matches!(result, Err(ConsoleError::Parse(_))));
+    let answer = console.execute("status 500", &mut |_| {}).unwrap();
+    assert_eq!(answer.result, Ok("status 500".to_string()));
+}
+
+#[test]
+fn missing_answer_times_out() {
+    let (port, _) = start_board();
+    let mut console = Console::new(port, Duration::from_millis(300));
+
+    let result = console.execute("mode text", &mut |_| {});
+    assert!(matches!(result, Err(ConsoleError::Timeout(Commands::Mode(_)))));
+    let answer = console.execute("state", &mut |_| {}).unwrap();
+    assert_eq!(answer.result, Ok("state".to_string()));
+}
+
+fn run_script(path: &str, script: &str) -> (bool, String) {
+    let mut cli = Command::new(env!("CARGO_BIN_EXE_nucleo-cli"))
+        .args([path, "--timeout", "500", "--script", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    cli.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    let output = cli.wait_with_output().unwrap();
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn script_mode() {
+    let (_port, path) = start_board();
+    let (success, stdout) = run_script(&path, "# regression\nled g 250\n\nlight 1 single\n");
+    assert!(success, "{}", stdout);
+    assert_eq!(
+        stdout,
+        "> led g 250\n\
+         [status] light sensor 1 single, 42.0 lux; light sensor 2 off, no value yet; temperature off, no value yet; i2c recoveries 0\n\
+         OK led g 250\n\
+         > light 1 single\n\
+         [status] light sensor 1 single, 42.0 lux; light sensor 2 off, no value yet; temperature off, no value yet; i2c recoveries 0\n\
+         VAL light 1 42.0\n\
+         OK light 1 single\n"
+    );
+
+    let (success, stdout) = run_script(&path, "led b on\nstatus 0\nled b off\n");
+    assert!(!success);
+    assert!(!stdout.contains("led b off"), "the script stops at the first error");
+}
@@ -0,0 +1,51 @@
+//! The time between two samples of a sensor that fails, see [backoff_ms].
+//!
+//! A sensor that is unplugged or on a stuck bus fails every sample. The period is doubled for
+//! each fault in a row, such that it does not keep the bus busy and fill the console with
+//! errors, up to [MAX_BACKOFF_MS]. The first sample that succeeds returns to the period.
+
+/// The longest time between two samples of a faulty sensor in ms, unless its period is longer
+pub const MAX_BACKOFF_MS: u32 = 60_000;
+
+/// The period is doubled for each fault in a row up to this many times
+const MAX_BACKOFF_DOUBLINGS: u32 = 6;
+
+/// Gives the time in ms between two samples after the given number of faults in a row
+pub fn backoff_ms(period_ms: u32, faults: u32) -> u32 {
+    if faults == 0 {
+        return period_ms;
+    }
+    let delay = period_ms.saturating_mul(1 << faults.min(MAX_BACKOFF_DOUBLINGS));
+    delay.min(MAX_BACKOFF_MS.max(period_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_per_fault() {
+        assert_eq!(backoff_ms(150, 0), 150);
+        assert_eq!(backoff_ms(150, 1), 300);
+        assert_eq!(backoff_ms(150, 2), 600);
+        assert_eq!(backoff_ms(150, 6), 9_600);
+        // the doublings end before the cap is reached
+        assert_eq!(backoff_ms(150, 7), 9_600);
+        assert_eq!(backoff_ms(150, u32::MAX), 9_600);
+    }
+
+    #[test]
+    fn is_capped() {
+        assert_eq!(backoff_ms(1_000, 5), 32_000);
+        assert_eq!(backoff_ms(1_000, 6), MAX_BACKOFF_MS);
+        assert_eq!(backoff_ms(10_000, 3), MAX_BACKOFF_MS);
+    }
+
+    #[test]
+    fn keeps_a_period_beyond_the_cap() {
+        assert_eq!(backoff_ms(120_000, 0), 120_000);
+        assert_eq!(backoff_ms(120_000, 1), 120_000);
+        assert_eq!(backoff_ms(3_600_000, 6), 3_600_000);
+        assert_eq!(backoff_ms(u32::MAX, 6), u32::MAX);
+    }
+}
@@ -0,0 +1,163 @@
+//! Bus errors reduced to what is shown to the operator, see [BusFault] and [OneWireFault].
+//!
+//! The error types of the HALs differ from board to board, a [BusFault] keeps the kind of the
+//! error and the address of the device, such that it can be sent over the console and shown
+//! in the status report, e.g. `NACK @0x23`. A [OneWireFault] does the same for the 1-Wire bus.
+//! The drivers classify the errors of the HALs into these faults.
+
+use core::fmt;
+use core::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BusFaultKind {
+    /// the device did not acknowledge its address or data
+    Nack,
+
+    /// another master took over the bus
+    ArbitrationLoss,
+
+    /// a misplaced start or stop condition was detected
+    Bus,
+
+    /// the peripheral could not keep up with the data
+    Overrun,
+
+    /// the transaction did not complete in time
+    Timeout,
+
+    /// any other error of the HAL
+    Other,
+}
+
+/// A failed transaction with the device at the given address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BusFault {
+    pub kind: BusFaultKind,
+    pub addr: u8,
+}
+
+impl BusFaultKind {
+    const ALL: [BusFaultKind; 6] = [
+        BusFaultKind::Nack,
+        BusFaultKind::ArbitrationLoss,
+        BusFaultKind::Bus,
+        BusFaultKind::Overrun,
+        BusFaultKind::Timeout,
+        BusFaultKind::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BusFaultKind::Nack => "NACK",
+            BusFaultKind::ArbitrationLoss => "arbitration loss",
+            BusFaultKind::Bus => "bus error",
+            BusFaultKind::Overrun => "overrun",
+            BusFaultKind::Timeout => "timeout",
+            BusFaultKind::Other => "failure",
+        }
+    }
+}
+
+/// The text is not in the form given by the [Display](fmt::Display) of [BusFault]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseBusFaultError;
+
+impl fmt::Display for BusFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} @0x{:02X}", self.kind.as_str(), self.addr)
+    }
+}
+
+impl FromStr for BusFault {
+    type Err = ParseBusFaultError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (kind, addr) = text.rsplit_once(" @0x").ok_or(ParseBusFaultError)?;
+        let kind = BusFaultKind::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == kind)
+            .ok_or(ParseBusFaultError)?;
+        let addr = u8::from_str_radix(addr, 16).map_err(|_| ParseBusFaultError)?;
+        Ok(BusFault { kind, addr })
+    }
+}
+
+/// A failed transaction on the 1-Wire bus, the pin error of the HAL is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OneWireFault {
+    /// no device answered the reset
+    NoPresence,
+
+    /// the CRC of the data read from the device does not match
+    Crc,
+
+    /// the pin could not be driven or read
+    Pin,
+}
+
+impl OneWireFault {
+    const ALL: [OneWireFault; 3] = [OneWireFault::NoPresence, OneWireFault::Crc, OneWireFault::Pin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OneWireFault::NoPresence => "no device",
+            OneWireFault::Crc => "CRC mismatch",
+            OneWireFault::Pin => "pin failure",
+        }
+    }
+}
+
+/// The text is not in the form given by the [Display](fmt::Display) of [OneWireFault]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOneWireFaultError;
+
+impl fmt::Display for OneWireFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OneWireFault {
+    type Err = ParseOneWireFaultError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        OneWireFault::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == text)
+            .ok_or(ParseOneWireFaultError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn display_roundtrip() {
+        let fault = BusFault { kind: BusFaultKind::Nack, addr: 0x23 };
+        assert_eq!(fault.to_string(), "NACK @0x23");
+
+        for kind in BusFaultKind::ALL {
+            let fault = BusFault { kind, addr: 0x5C };
+            assert_eq!(fault.to_string().parse(), Ok(fault));
+        }
+        for text in ["NACK", "NACK @0x", "NACK @0x123", "lost @0x23"] {
+            assert_eq!(text.parse::<BusFault>(), Err(ParseBusFaultError), "{}", text);
+        }
+    }
+
+    #[test]
+    fn onewire_display_roundtrip() {
+        assert_eq!(OneWireFault::Crc.to_string(), "CRC mismatch");
+        for fault in OneWireFault::ALL {
+            assert_eq!(fault.to_string().parse(), Ok(fault));
+        }
+        assert_eq!("crc".parse::<OneWireFault>(), Err(ParseOneWireFaultError));
+    }
+}
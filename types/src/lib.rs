@@ -0,0 +1,26 @@
+#![no_std]
+
+//! This crate contains the wire-level types of the sensor platform.
+//!
+//! They are what the drivers measure and the console sends, such that the drivers and the
+//! command protocol share them without depending on each other:
+//!
+//! - [x] Fixed-point units like [Lux](units::Lux) and [Celsius](units::Celsius)
+//! - [x] Bus errors reduced to their kind and device, e.g. `NACK @0x23`
+//! - [x] The ROM and power mode of 1-Wire slaves, validated with the Dallas/Maxim CRC-8
+//! - [x] The probed I2C addresses and the names of the known parts
+//! - [x] Backing off from a sensor that fails, see [backoff_ms]
+//!
+//! With the feature `serde` the types may be serialized.
+
+pub mod backoff;
+pub mod error;
+pub mod onewire;
+pub mod scan;
+pub mod units;
+
+pub use backoff::{backoff_ms, MAX_BACKOFF_MS};
+pub use error::{BusFault, BusFaultKind, OneWireFault};
+pub use onewire::{crc8, known_family, PowerMode, Rom};
+pub use scan::{known_device, SCAN_RANGE};
+pub use units::{Celsius, Lux};
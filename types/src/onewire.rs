@@ -0,0 +1,173 @@
+//! The identity of the slaves on a 1-Wire bus, see [Rom] and [PowerMode].
+//!
+//! A ROM that is read from the bus is validated with the Dallas/Maxim CRC-8, see [crc8], which
+//! protects the data of the slaves too. Known parts are named by their family code, see
+//! [known_family].
+
+use core::fmt;
+
+const KNOWN_FAMILIES: [(u8, &str); 1] = [(0x28, "DS18B20")];
+
+/// Gives the name of the part with the given family code
+pub fn known_family(family_code: u8) -> Option<&'static str> {
+    KNOWN_FAMILIES
+        .iter()
+        .find(|(known, _)| *known == family_code)
+        .map(|(_, name)| *name)
+}
+
+/// The Dallas/Maxim CRC-8 (polynomial x^8 + x^5 + x^4 + 1, reflected, initial value 0)
+///
+/// It protects the ROM and the data of the slaves, the CRC over data and its CRC byte is 0.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// The 64 bit ROM code that identifies a slave, as it is sent on the bus
+///
+/// The first byte is the family code, e.g. 0x28 for a DS18B20, the next six are the serial
+/// number with its least significant byte first and the last is a CRC of the others.
+///
+/// It is displayed like the Linux 1-Wire subsystem names the devices, e.g. `28-00000f1e64ff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rom(pub [u8; 8]);
+
+impl Rom {
+    pub fn family_code(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// The 48 bit serial number
+    pub fn serial(&self) -> u64 {
+        self.0[1..7]
+            .iter()
+            .rev()
+            .fold(0, |serial, byte| serial << 8 | *byte as u64)
+    }
+
+    pub fn crc(&self) -> u8 {
+        self.0[7]
+    }
+
+    /// Indicates if the CRC matches the other bytes and the family code is set
+    ///
+    /// A bus that is stuck low reads as zeros, whose CRC matches as well, no family has the code 0.
+    pub fn is_valid(&self) -> bool {
+        self.family_code() != 0 && crc8(&self.0) == 0
+    }
+
+    /// The bit in the order it is sent on the bus, 0 is the least significant bit of the family code
+    pub fn bit(&self, idx: u8) -> bool {
+        self.0[idx as usize / 8] & (1 << (idx % 8)) != 0
+    }
+
+    /// Changes the bit in the order it is sent on the bus, see [Rom::bit]
+    pub fn set_bit(&mut self, idx: u8, bit: bool) {
+        let mask = 1 << (idx % 8);
+        if bit {
+            self.0[idx as usize / 8] |= mask;
+        } else {
+            self.0[idx as usize / 8] &= !mask;
+        }
+    }
+}
+
+impl fmt::Display for Rom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}-{:012x}", self.family_code(), self.serial())
+    }
+}
+
+/// How a slave is powered, as told by the Read Power Supply command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PowerMode {
+    /// by a third wire to VDD
+    #[default]
+    External,
+
+    /// by the data line, needs the strong pull-up for conversions and EEPROM writes
+    Parasitic,
+}
+
+impl PowerMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowerMode::External => "external",
+            PowerMode::Parasitic => "parasitic",
+        }
+    }
+}
+
+impl fmt::Display for PowerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    const ROM_A: [u8; 8] = [0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x34];
+    const ROM_D: [u8; 8] = [0x10, 0x3D, 0x12, 0x6B, 0x0B, 0x08, 0x00, 0x10];
+
+    #[test]
+    fn crc8_check_values() {
+        // the example of Maxim application note 27
+        assert_eq!(crc8(&[0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00]), 0xA2);
+        assert!(Rom([0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA2]).is_valid());
+        assert_eq!(crc8(&[]), 0);
+        for rom in [ROM_A, ROM_D] {
+            assert!(Rom(rom).is_valid());
+        }
+        assert!(!Rom([0x28, 0xFF, 0x64, 0x1E, 0x0F, 0x00, 0x00, 0x35]).is_valid());
+    }
+
+    #[test]
+    fn rejects_family_code_zero() {
+        assert_eq!(crc8(&[0; 8]), 0);
+        assert!(!Rom([0; 8]).is_valid());
+
+        let mut rom = [0x00, 0x3D, 0x12, 0x6B, 0x0B, 0x08, 0x00, 0x00];
+        rom[7] = crc8(&rom[..7]);
+        assert!(!Rom(rom).is_valid());
+        assert!(!Rom([0xFF; 8]).is_valid());
+    }
+
+    #[test]
+    fn rom_fields() {
+        let rom = Rom(ROM_A);
+        assert_eq!(rom.family_code(), 0x28);
+        assert_eq!(rom.serial(), 0x0000_0F1E_64FF);
+        assert_eq!(rom.crc(), 0x34);
+        assert_eq!(std::format!("{}", rom), "28-00000f1e64ff");
+
+        let mut bits = Rom::default();
+        for idx in 0..64 {
+            bits.set_bit(idx, rom.bit(idx));
+        }
+        assert_eq!(bits, rom);
+    }
+
+    #[test]
+    fn names_known_families() {
+        assert_eq!(known_family(0x28), Some("DS18B20"));
+        assert_eq!(known_family(0x10), None);
+    }
+}
@@ -0,0 +1,34 @@
+//! The addresses an I2C scan probes, see [SCAN_RANGE], and the parts known at them.
+//!
+//! The addresses outside of the range are reserved by the I2C specification. Addresses of
+//! devices that are supported by a driver of the platform are named by [known_device], such
+//! that a scan tells which part is on the bus.
+
+use core::ops::RangeInclusive;
+
+/// The 7 bit addresses that are not reserved and therefore probed
+pub const SCAN_RANGE: RangeInclusive<u8> = 0x08..=0x77;
+
+/// The BH1750FVI with its ADDR pin low and high
+const KNOWN_DEVICES: [(u8, &str); 2] = [(0x23, "BH1750"), (0x5C, "BH1750")];
+
+/// Gives the name of the part a driver expects at the given address
+pub fn known_device(addr: u8) -> Option<&'static str> {
+    KNOWN_DEVICES
+        .iter()
+        .find(|(known, _)| *known == addr)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_known_devices() {
+        assert_eq!(known_device(0x23), Some("BH1750"));
+        assert_eq!(known_device(0x5C), Some("BH1750"));
+        assert_eq!(known_device(0x40), None);
+        assert!(KNOWN_DEVICES.iter().all(|(addr, _)| SCAN_RANGE.contains(addr)));
+    }
+}
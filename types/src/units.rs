@@ -0,0 +1,168 @@
+//! Typed physical quantities as measured by the sensors.
+//!
+//! The values are fixed-point integers, such that no floating point support is needed on the
+//! target. Their [Display](fmt::Display) is used by the console and the status report and is
+//! parsed back by [FromStr].
+
+use core::fmt;
+use core::str::FromStr;
+
+/// An illuminance with a resolution of 1 milli lux
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lux(u32);
+
+impl Lux {
+    pub const fn from_millilux(millilux: u32) -> Self {
+        Lux(millilux)
+    }
+
+    pub const fn from_lux(lux: u32) -> Self {
+        Lux(lux * 1000)
+    }
+
+    pub const fn millilux(&self) -> u32 {
+        self.0
+    }
+
+    /// The whole lux, the fraction is truncated
+    pub const fn lux(&self) -> u32 {
+        self.0 / 1000
+    }
+}
+
+/// The text is no decimal number with at most three decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLuxError;
+
+impl fmt::Display for Lux {
+    /// Gives the lux with one decimal, e.g. `28066.6`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.0 / 1000, self.0 % 1000 / 100)
+    }
+}
+
+impl FromStr for Lux {
+    type Err = ParseLuxError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if whole.is_empty() || fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseLuxError);
+        }
+
+        let whole: u32 = whole.parse().map_err(|_| ParseLuxError)?;
+        // right pad the decimals to milli lux
+        let millis = fraction
+            .bytes()
+            .chain(core::iter::repeat(b'0'))
+            .take(3)
+            .fold(0, |acc, digit| acc * 10 + (digit - b'0') as u32);
+
+        whole
+            .checked_mul(1000)
+            .and_then(|millilux| millilux.checked_add(millis))
+            .map(Lux)
+            .ok_or(ParseLuxError)
+    }
+}
+
+/// A temperature with a resolution of 1 milli degree Celsius
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Celsius(i32);
+
+impl Celsius {
+    pub const fn from_millidegrees(millidegrees: i32) -> Self {
+        Celsius(millidegrees)
+    }
+
+    pub const fn from_degrees(degrees: i32) -> Self {
+        Celsius(degrees * 1000)
+    }
+
+    pub const fn millidegrees(&self) -> i32 {
+        self.0
+    }
+
+    /// The whole degrees, the fraction is truncated towards zero
+    pub const fn degrees(&self) -> i32 {
+        self.0 / 1000
+    }
+}
+
+/// The text is no signed decimal number with at most three decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseCelsiusError;
+
+impl fmt::Display for Celsius {
+    /// Gives the degrees with two decimals, e.g. `21.06` or `-10.12`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{}{}.{:02}", sign, abs / 1000, abs % 1000 / 10)
+    }
+}
+
+impl FromStr for Celsius {
+    type Err = ParseCelsiusError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        // the magnitude has the same syntax as a lux value, but no sign of its own
+        if text.starts_with('+') {
+            return Err(ParseCelsiusError);
+        }
+        let magnitude = text.parse::<Lux>().map_err(|_| ParseCelsiusError)?;
+        let millis = i32::try_from(magnitude.millilux()).map_err(|_| ParseCelsiusError)?;
+        Ok(Celsius(if negative { -millis } else { millis }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(Lux::from_millilux(28_066_666).to_string(), "28066.6");
+        assert_eq!(Lux::from_millilux(500).to_string(), "0.5");
+        assert_eq!(Lux::from_lux(42).to_string(), "42.0");
+        assert_eq!(Lux::default().to_string(), "0.0");
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("28066.6".parse(), Ok(Lux::from_millilux(28_066_600)));
+        assert_eq!("42".parse(), Ok(Lux::from_lux(42)));
+        assert_eq!("0.125".parse(), Ok(Lux::from_millilux(125)));
+        for text in ["", ".5", "1.2345", "1.x", "-1", "lux", "4294968"] {
+            assert_eq!(text.parse::<Lux>(), Err(ParseLuxError), "{}", text);
+        }
+    }
+
+    #[test]
+    fn celsius_display() {
+        assert_eq!(Celsius::from_millidegrees(21_062).to_string(), "21.06");
+        assert_eq!(Celsius::from_millidegrees(-10_125).to_string(), "-10.12");
+        assert_eq!(Celsius::from_millidegrees(-500).to_string(), "-0.50");
+        assert_eq!(Celsius::from_degrees(85).to_string(), "85.00");
+        assert_eq!(Celsius::default().to_string(), "0.00");
+    }
+
+    #[test]
+    fn celsius_parse() {
+        assert_eq!("21.06".parse(), Ok(Celsius::from_millidegrees(21_060)));
+        assert_eq!("-10.125".parse(), Ok(Celsius::from_millidegrees(-10_125)));
+        assert_eq!("-55".parse(), Ok(Celsius::from_degrees(-55)));
+        for text in ["", "-", "--1", "+1", "1.2345", "°C", "2147484"] {
+            assert_eq!(text.parse::<Celsius>(), Err(ParseCelsiusError), "{}", text);
+        }
+    }
+}